@@ -1,23 +1,35 @@
+use crate::clock::{real_clock, SharedClock};
+use crate::hasher::DefaultBuildHasher;
+use crate::shared::ghost_history::GhostHistory;
+use crate::shared::hit_ratio::HitRatio;
 use crate::shared::item::Item;
 use crate::shared::traits::bucket::Bucket;
-use crate::shared::utils::get_index;
+use crate::shared::utils::{get_index, into_pairs};
 use associative_cache_bucket::AssociativeCacheBucket;
 use associative_cache_settings::AssociativeCacheSettings;
 
 use std::fmt::{Debug, Display, Formatter};
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
 
 mod associative_cache_bucket;
 pub mod associative_cache_jni;
 pub mod associative_cache_settings;
 
 #[derive(Debug)]
-pub struct AssociativeCache<K, V>
+pub struct AssociativeCache<K, V, S = DefaultBuildHasher>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
-	pub buckets: Vec<AssociativeCacheBucket<K, V>>,
+	pub buckets: Vec<AssociativeCacheBucket<K, V, S>>,
+	/// Hits and misses of `get_and_update_item`, see `hit_ratio`.
+	hit_ratio: HitRatio,
+	/// Time to live of the items put without one, `None` if they never expire.
+	default_ttl: Option<Duration>,
+	clock: SharedClock,
+	hash_builder: S,
 }
 
 impl<K, V> AssociativeCache<K, V>
@@ -25,37 +37,148 @@ where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
 {
+	/// Creates a new cache with the given settings and the default hasher.
 	pub fn new(settings: AssociativeCacheSettings) -> Self {
+		Self::with_hasher(settings, DefaultBuildHasher::default())
+	}
+}
+
+impl<K, V, S> AssociativeCache<K, V, S>
+where
+	K: Hash + Eq + Copy + Debug,
+	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
+{
+	/// Creates a new cache with the given settings, hashing keys with `hash_builder`.
+	pub fn with_hasher(settings: AssociativeCacheSettings, hash_builder: S) -> Self {
+		Self::with_clock(settings, hash_builder, real_clock())
+	}
+
+	/// Like `with_hasher`, taking the time from `clock` rather than from the wall clock.
+	pub fn with_clock(settings: AssociativeCacheSettings, hash_builder: S, clock: SharedClock) -> Self {
 		let mut buckets = Vec::new();
-		buckets.push(AssociativeCacheBucket::new(settings.clone()));
-		Self { buckets }
+		for _ in 0..settings.num_of_buckets {
+			buckets.push(AssociativeCacheBucket::new(settings.clone(), hash_builder.clone()).with_clock(clock.clone()));
+		}
+		Self {
+			buckets,
+			hit_ratio: HitRatio::default(),
+			default_ttl: settings.default_ttl,
+			clock,
+			hash_builder,
+		}
+	}
+
+	/// Insert a key-value pair into the cache, or replace the value if the key already exists.
+	///
+	/// Returns the previous value of the key, if any, and the key-value pairs evicted to make room for the new one.
+	/// As a side effect, replacing a value makes updates according to the eviction policy.
+	pub fn put(&mut self, key: K, value: V) -> (Option<V>, Vec<(K, V)>) {
+		self.put_item(key, value, |item| item)
+	}
+
+	/// Like `put`, for an item taking `size` bytes of the capacity of its bucket.
+	///
+	/// An existing key takes the new size, evicting other items of its bucket if it no longer fits.
+	/// A plain `put` gives it the size of an item without one again.
+	pub fn put_with_size(&mut self, key: K, value: V, size: usize) -> (Option<V>, Vec<(K, V)>) {
+		self.put_item(key, value, |item| item.with_size(size))
+	}
+
+	/// Like `put`, for an item that expires `ttl` from now.
+	///
+	/// An expired item is never returned and its slot is reclaimed before any live item is evicted.
+	/// Replacing the value of an existing key also replaces its expiration time.
+	pub fn put_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> (Option<V>, Vec<(K, V)>) {
+		self.put_item(key, value, |item| item.with_ttl(ttl))
 	}
 
-	/// Insert a key-value pair into the cache
-	pub fn put(&mut self, key: K, value: V) {
-		let bucket = self.get_mut_bucket(&key);
-		bucket.put(Item::new(key, value));
+	/// Puts the item `build` makes out of a plain item created at the current time of the clock.
+	fn put_item(&mut self, key: K, value: V, build: impl FnOnce(Item<K, V>) -> Item<K, V>) -> (Option<V>, Vec<(K, V)>) {
+		self.clock.on_request();
+		let now = self.clock.now();
+		let mut item = build(Item::new_at(key, value, now));
+		if item.expires_at.is_none() {
+			item.expires_at = self.default_ttl.map(|ttl| now + ttl);
+		}
+		let bucket_index = get_index(&item.key, self.buckets.len(), &self.hash_builder);
+		let bucket = &mut self.buckets[bucket_index];
+		if let Some(position) = bucket.get_position(&item.key) {
+			if !bucket.get_items()[position].is_expired(now) {
+				let previous_value = bucket.get_items()[position].value;
+				let (_, evicted_items) = bucket.put(item);
+				return (Some(previous_value), into_pairs(evicted_items));
+			}
+			bucket.remove_item(position);
+		}
+		// A new key is put after a miss, which tells how many bytes were missed
+		self.hit_ratio.record_miss_bytes(item.get_size());
+		if !bucket.has_room_for(&item) {
+			// Expired items make room before any live item is evicted
+			bucket.remove_expired_items(now);
+		}
+		let (_, evicted_items) = bucket.put(item);
+		(None, into_pairs(evicted_items))
 	}
 
 	/// Returns the value of key if exists wrapper in Some ans None otherwise
 	///
 	/// As a side effect makes updates according to the eviction policy.
 	pub fn get_and_update_item(&mut self, key: &K) -> Option<&V> {
-		let bucket = self.get_mut_bucket(key);
-		let data = bucket.get(key)?;
+		let bucket_index = get_index(key, self.buckets.len(), &self.hash_builder);
+		self.clock.on_request();
+		let now = self.clock.now();
+		let bucket = &mut self.buckets[bucket_index];
+		if bucket
+			.get_position(key)
+			.is_some_and(|position| bucket.get_items()[position].is_expired(now))
+		{
+			bucket.remove(key);
+			self.hit_ratio.record_miss();
+			return None;
+		}
+		let Some(data) = bucket.get(key) else {
+			self.hit_ratio.record_miss();
+			return None;
+		};
+		self.hit_ratio.record_hit(data.get_size());
 		Some(&data.value)
 	}
 
-	fn get_mut_bucket(&mut self, key: &K) -> &mut AssociativeCacheBucket<K, V> {
-		let bucket_index = get_index(key, self.buckets.len());
+	/// Returns the object and byte hit ratios, counting a hit or a miss for every `get_and_update_item`.
+	/// The bytes of a miss are counted when `put` gets the new key, which is how a simulator drives the cache.
+	pub fn hit_ratio(&self) -> &HitRatio {
+		&self.hit_ratio
+	}
+
+	/// Removes the key from the cache and returns its value if it was present and not expired.
+	pub fn remove(&mut self, key: &K) -> Option<V> {
+		let bucket = self.get_mut_bucket(key);
+		let item = bucket.remove(key)?;
+		(!item.is_expired(self.clock.now())).then_some(item.value)
+	}
+
+	/// Returns the number of bytes the ghost histories of all the buckets take.
+	pub fn ghost_memory_usage(&self) -> usize {
+		self
+			.buckets
+			.iter()
+			.filter_map(|bucket| bucket.get_policy_state().get_ghost())
+			.map(GhostHistory::memory_usage)
+			.sum()
+	}
+
+	fn get_mut_bucket(&mut self, key: &K) -> &mut AssociativeCacheBucket<K, V, S> {
+		let bucket_index = get_index(key, self.buckets.len(), &self.hash_builder);
 		&mut self.buckets[bucket_index]
 	}
 }
 
-impl<K, V> Display for AssociativeCache<K, V>
+impl<K, V, S> Display for AssociativeCache<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug + Display,
 	V: Eq + Copy + Debug + Display,
+	S: BuildHasher + Clone + Debug,
 {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		for bucket in &self.buckets {
@@ -64,3 +187,89 @@ where
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::eviction_policy::EvictionPolicy;
+
+	#[test]
+	fn test_put_and_remove() {
+		let mut cache: AssociativeCache<i64, i64> = AssociativeCache::new(AssociativeCacheSettings {
+			num_of_buckets: 2,
+			..AssociativeCacheSettings::default()
+		});
+		assert_eq!(cache.put(1, 10), (None, vec![]));
+		assert_eq!(cache.put(1, 11), (Some(10), vec![]));
+		cache.put(2, 20);
+
+		assert_eq!(cache.remove(&1), Some(11));
+		assert_eq!(cache.remove(&1), None);
+		assert_eq!(cache.get_and_update_item(&1), None);
+		assert_eq!(cache.get_and_update_item(&2), Some(&20));
+	}
+
+	#[test]
+	fn test_expired_items_are_invisible_and_reclaimed_first() {
+		let mut cache: AssociativeCache<i64, i64> = AssociativeCache::new(AssociativeCacheSettings {
+			bucket_size: 2,
+			..AssociativeCacheSettings::default()
+		});
+		cache.put(0, 0);
+		cache.put_with_ttl(1, 1, Duration::ZERO);
+		assert_eq!(cache.get_and_update_item(&1), None);
+
+		cache.put_with_ttl(2, 2, Duration::ZERO);
+		// 2 expired, so 0 stays even though it is the least recently used item
+		assert_eq!(cache.put(3, 3), (None, vec![]));
+		assert_eq!(cache.get_and_update_item(&0), Some(&0));
+		assert_eq!(cache.remove(&2), None);
+	}
+
+	#[test]
+	fn test_put_with_size_resizes_existing_keys() {
+		let mut cache: AssociativeCache<i64, i64> = AssociativeCache::new(AssociativeCacheSettings {
+			bucket_size: 4,
+			bucket_capacity_bytes: Some(4),
+			..AssociativeCacheSettings::default()
+		});
+		for key in 0..4 {
+			cache.put_with_size(key, key, 1);
+		}
+		// 0 grows to 3 bytes, the least recently used items make room for it
+		assert_eq!(cache.put_with_size(0, 10, 3), (Some(0), vec![(1, 1), (2, 2)]));
+		assert_eq!(cache.get_and_update_item(&2), None);
+		assert_eq!(cache.get_and_update_item(&0), Some(&10));
+
+		// Shrinking never evicts
+		assert_eq!(cache.put_with_size(0, 11, 1), (Some(10), vec![]));
+		assert_eq!(cache.put_with_size(1, 1, 1), (None, vec![]));
+		assert_eq!(cache.put_with_size(2, 2, 1), (None, vec![]));
+	}
+
+	#[test]
+	fn test_every_eviction_policy_keeps_items_consistent() {
+		for eviction_policy in (0..).map_while(EvictionPolicy::from_usize) {
+			let mut cache: AssociativeCache<i64, i64> = AssociativeCache::new(AssociativeCacheSettings {
+				num_of_buckets: 4,
+				bucket_size: 4,
+				eviction_policy: eviction_policy.clone(),
+				..AssociativeCacheSettings::default()
+			});
+			for round in 0..1000_i64 {
+				let key = (round * 7919) % 53;
+				if cache.get_and_update_item(&key).is_none() {
+					cache.put(key, key * 2);
+				}
+			}
+
+			let cached = (0..53).filter(|key| cache.get_and_update_item(key).is_some()).count();
+			assert!(cached <= 16, "{:?} holds {} items", eviction_policy, cached);
+			assert!(
+				(0..53).all(|key| cache.get_and_update_item(&key).is_none_or(|&value| value == key * 2)),
+				"{:?}",
+				eviction_policy
+			);
+		}
+	}
+}
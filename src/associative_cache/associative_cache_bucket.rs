@@ -1,40 +1,67 @@
 use super::associative_cache_settings::AssociativeCacheSettings;
+use crate::clock::{real_clock, SharedClock};
 use crate::eviction_policy::EvictionPolicy;
 use crate::shared::item::Item;
+use crate::shared::policy_state::BucketPolicyState;
 use crate::shared::traits::bucket::Bucket;
+use crate::shared::utils::{get_ghost_capacity, get_protected_size, hash};
 
 use std::fmt::{Debug, Display, Formatter};
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 
 #[derive(Debug)]
-pub struct AssociativeCacheBucket<K, V>
+pub struct AssociativeCacheBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
 	items: Vec<Item<K, V>>,
 	max_size: usize,
+	/// Capacity in bytes, `None` if only the number of items is limited.
+	max_bytes: Option<usize>,
+	max_protected_size: usize,
 	eviction_policy: EvictionPolicy,
+	policy_state: BucketPolicyState<K>,
+	clock: SharedClock,
+	/// The hasher of the cache, the ghost history remembers keys by their hash.
+	hash_builder: S,
 }
 
-impl<K, V> AssociativeCacheBucket<K, V>
+impl<K, V, S> AssociativeCacheBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
-	pub fn new(settings: AssociativeCacheSettings) -> Self {
+	pub fn new(settings: AssociativeCacheSettings, hash_builder: S) -> Self {
 		Self {
 			items: Vec::new(),
 			max_size: settings.bucket_size,
+			max_bytes: settings.bucket_capacity_bytes,
+			max_protected_size: get_protected_size(settings.bucket_size, settings.protected_fraction),
+			policy_state: BucketPolicyState::new(
+				&settings.eviction_policy,
+				get_ghost_capacity(settings.bucket_size, settings.ghost_capacity),
+			),
 			eviction_policy: settings.eviction_policy,
+			clock: real_clock(),
+			hash_builder,
 		}
 	}
+
+	/// Takes the timestamps of the items from `clock` rather than from the wall clock.
+	pub fn with_clock(mut self, clock: SharedClock) -> Self {
+		self.clock = clock;
+		self
+	}
 }
 
-impl<K, V> Bucket<K, V> for AssociativeCacheBucket<K, V>
+impl<K, V, S> Bucket<K, V> for AssociativeCacheBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
 	fn get_items(&self) -> &Vec<Item<K, V>> {
 		&self.items
@@ -48,15 +75,40 @@ where
 		self.max_size
 	}
 
+	fn get_max_bytes(&self) -> Option<usize> {
+		self.max_bytes
+	}
+
 	fn get_eviction_policy(&self) -> &EvictionPolicy {
 		&self.eviction_policy
 	}
+
+	fn get_clock(&self) -> &SharedClock {
+		&self.clock
+	}
+
+	fn get_max_protected_size(&self) -> usize {
+		self.max_protected_size
+	}
+
+	fn get_policy_state(&self) -> &BucketPolicyState<K> {
+		&self.policy_state
+	}
+
+	fn get_policy_state_mut(&mut self) -> &mut BucketPolicyState<K> {
+		&mut self.policy_state
+	}
+
+	fn get_ghost_fingerprint(&self, key: &K) -> u64 {
+		hash(key, &self.hash_builder)
+	}
 }
 
-impl<K, V> Display for AssociativeCacheBucket<K, V>
+impl<K, V, S> Display for AssociativeCacheBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug + Display,
 	V: Eq + Copy + Debug + Display,
+	S: BuildHasher + Clone + Debug,
 {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		for item in &self.items {
@@ -1,39 +1,108 @@
 use super::associative_cache_settings::AssociativeCacheSettings;
 use super::AssociativeCache;
 use crate::eviction_policy::EvictionPolicy;
+use crate::hasher::HasherKind;
+use log::error;
+use std::time::Duration;
 
-use jni::{objects::JClass, sys::jlong, JNIEnv};
+use jni::{
+	objects::JClass,
+	sys::{jboolean, jdouble, jlong},
+	JNIEnv,
+};
 
-type AssociativeCacheTy = AssociativeCache<i64, i64>;
+type AssociativeCacheTy = AssociativeCache<i64, i64, HasherKind>;
 
 #[no_mangle]
 pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_initCache<
 	'local,
 >(
-	_env: JNIEnv,
-	_class: JClass,
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
 	num_of_buckets: jlong,
 	bucket_size: jlong,
 	eviction_policy: jlong,
 ) -> jlong {
+	// The hasher with id 0 is the default one, the seed is ignored
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_initCacheWithHasher(
+		env,
+		class,
+		num_of_buckets,
+		bucket_size,
+		eviction_policy,
+		0,
+		0,
+	)
+}
+
+/// Like `initCache`, hashing keys with the hasher `HasherKind::from_usize` gives for `hasher` and `hasher_seed`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_initCacheWithHasher<
+	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	num_of_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+) -> jlong {
+	// A bucket capacity of 0 bytes only limits the number of items
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_initCacheWithBucketCapacityBytes(
+		env,
+		class,
+		num_of_buckets,
+		bucket_size,
+		eviction_policy,
+		hasher,
+		hasher_seed,
+		0,
+	)
+}
+
+/// Like `initCacheWithHasher`, limiting every bucket to `bucket_capacity_bytes` bytes on top of `bucket_size` items,
+/// 0 to only limit the number of items. The items take the size given to `putToCacheWithSize`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_initCacheWithBucketCapacityBytes<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	num_of_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+	bucket_capacity_bytes: jlong,
+) -> jlong {
+	// Unknown ids are refused with a null pointer instead of unwinding into the JVM
+	let Some(eviction_policy) = EvictionPolicy::from_usize(eviction_policy as usize) else {
+		error!("new - unknown eviction policy {}", eviction_policy);
+		return 0;
+	};
+	let Some(hash_builder) = HasherKind::from_usize(hasher as usize, hasher_seed as u64) else {
+		error!("new - unknown hasher {}", hasher);
+		return 0;
+	};
 	let settings = AssociativeCacheSettings {
 		num_of_buckets: num_of_buckets as usize,
 		bucket_size: bucket_size as usize,
-		eviction_policy: EvictionPolicy::from_usize(eviction_policy as usize).unwrap(),
+		eviction_policy,
+		bucket_capacity_bytes: (bucket_capacity_bytes > 0).then_some(bucket_capacity_bytes as usize),
+		..AssociativeCacheSettings::default()
 	};
 
-	let cache: AssociativeCacheTy = AssociativeCache::new(settings);
-	let cache_ptr = Box::into_raw(Box::new(cache)) as jlong;
-
-	cache_ptr
+	let cache: AssociativeCacheTy = AssociativeCache::with_hasher(settings, hash_builder);
+	Box::into_raw(Box::new(cache)) as jlong
 }
 
 #[no_mangle]
 pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_getFromCacheIfPresent<
 	'local,
 >(
-	_env: JNIEnv,
-	_class: JClass,
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
 	cache_ptr: jlong,
 	key: jlong,
 ) -> jlong {
@@ -49,14 +118,103 @@ pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_
 pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_putToCache<
 	'local,
 >(
-	_env: JNIEnv,
-	_class: JClass,
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
 	cache_ptr: jlong,
 	key: jlong,
 	value: jlong,
 ) {
 	let cache = unsafe { &mut *(cache_ptr as *mut AssociativeCacheTy) };
-	cache.put(key as i64, value as i64);
+	cache.put(key, value);
+}
+
+/// Like `putToCache`, returns whether the key already existed and its value was replaced.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_upsertToCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+) -> jboolean {
+	let cache = unsafe { &mut *(cache_ptr as *mut AssociativeCacheTy) };
+	let (previous_value, _) = cache.put(key, value);
+	previous_value.is_some() as jboolean
+}
+
+/// Like `upsertToCache`, for an item taking `size` bytes of the capacity of its bucket.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_putToCacheWithSize<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+	size: jlong,
+) -> jboolean {
+	let cache = unsafe { &mut *(cache_ptr as *mut AssociativeCacheTy) };
+	let (previous_value, _) = cache.put_with_size(key, value, size.max(0) as usize);
+	previous_value.is_some() as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_putToCacheWithTtl<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+	ttl_millis: jlong,
+) -> jboolean {
+	let cache = unsafe { &mut *(cache_ptr as *mut AssociativeCacheTy) };
+	let (previous_value, _) = cache.put_with_ttl(key, value, Duration::from_millis(ttl_millis.max(0) as u64));
+	previous_value.is_some() as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_removeFromCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+) -> jlong {
+	let cache = unsafe { &mut *(cache_ptr as *mut AssociativeCacheTy) };
+	let res = cache.remove(&key);
+	res.unwrap_or(-1)
+}
+
+/// Returns the share of the lookups that were hits, see `AssociativeCache::hit_ratio`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_getObjectHitRatio<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+) -> jdouble {
+	let cache = unsafe { &*(cache_ptr as *const AssociativeCacheTy) };
+	cache.hit_ratio().object_hit_ratio()
+}
+
+/// Returns the share of the looked up bytes that were hits, see `AssociativeCache::hit_ratio`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_associative_AssociativeCacheRustPolicy_getByteHitRatio<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+) -> jdouble {
+	let cache = unsafe { &*(cache_ptr as *const AssociativeCacheTy) };
+	cache.hit_ratio().byte_hit_ratio()
 }
 
 #[no_mangle]
@@ -67,5 +225,5 @@ pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_
 	_class: JClass<'local>,
 	cache_ptr: jlong,
 ) {
-	unsafe { Box::from_raw(cache_ptr as *mut AssociativeCacheTy) };
+	let _boxed_cache = unsafe { Box::from_raw(cache_ptr as *mut AssociativeCacheTy) };
 }
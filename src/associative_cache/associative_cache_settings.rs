@@ -1,10 +1,19 @@
 use crate::eviction_policy::EvictionPolicy;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct AssociativeCacheSettings {
 	pub num_of_buckets: usize,
 	pub bucket_size: usize,
 	pub eviction_policy: EvictionPolicy,
+	/// Share of a bucket the protected region of SLRU and 2Q may take.
+	pub protected_fraction: f64,
+	/// Capacity of a bucket in bytes, on top of `bucket_size` items. `None` to only limit the number of items.
+	pub bucket_capacity_bytes: Option<usize>,
+	/// Number of evicted keys every bucket remembers for 2Q and CLOCK-Pro. `None` for half the bucket size.
+	pub ghost_capacity: Option<usize>,
+	/// Time to live of the items put without one, `None` if they never expire.
+	pub default_ttl: Option<Duration>,
 }
 
 impl Default for AssociativeCacheSettings {
@@ -13,6 +22,10 @@ impl Default for AssociativeCacheSettings {
 			num_of_buckets: 1,
 			bucket_size: 8,
 			eviction_policy: EvictionPolicy::ClassicLRU,
+			protected_fraction: 0.8,
+			bucket_capacity_bytes: None,
+			ghost_capacity: None,
+			default_ttl: None,
 		}
 	}
 }
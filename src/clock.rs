@@ -0,0 +1,163 @@
+//! This module defines the clocks that can be plugged into `Dash` and `AssociativeCache`.
+//!
+//! Timestamps drive the recency of the LRU family, the access rates of hyperbolic caching and TTLs.
+//! With the real clock they depend on how fast the cache runs, so two runs of the same trace may evict
+//! different items. A virtual clock only moves when told to, by the trace timestamps or by the requests,
+//! which makes the eviction decisions reproducible and saves a syscall per access.
+//!
+//! # Examples
+//!
+//! ```
+//! use dash::clock::VirtualClock;
+//! use dash::dash::{dash_settings::DashSettings, Dash};
+//! use dash::hasher::DefaultBuildHasher;
+//! use std::{sync::Arc, time::Duration};
+//!
+//! let clock = Arc::new(VirtualClock::new());
+//! let mut cache: Dash<i64, i64> = Dash::with_clock(DashSettings::default(), DefaultBuildHasher::default(), clock.clone()).unwrap();
+//! cache.put_with_ttl(1, 2, Duration::from_secs(10));
+//! clock.advance_to(Duration::from_secs(10));
+//! assert_eq!(cache.get_and_update_item(&1), None);
+//! ```
+
+use std::{
+	fmt::Debug,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+pub trait Clock: Debug + Send + Sync {
+	/// Returns the current time.
+	fn now(&self) -> Instant;
+
+	/// Called by the cache once per request, before the request reads the time.
+	fn on_request(&self) {}
+
+	/// Moves a virtual clock to `elapsed` after its creation. The wall clock ignores it.
+	fn advance_to(&self, _elapsed: Duration) {}
+}
+
+/// A clock shared by a cache and all its segments and buckets.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Returns the wall clock, the default clock of the caches.
+pub fn real_clock() -> SharedClock {
+	Arc::new(RealClock)
+}
+
+/// Returns the clock with the given id: 0 for the wall clock, 1 for a `VirtualClock`.
+///
+/// The virtual clock advances by `tick` on every request, or only when advanced if `tick` is zero.
+pub fn clock_from_usize(index: usize, tick: Duration) -> Option<SharedClock> {
+	match index {
+		0 => Some(real_clock()),
+		1 => Some(Arc::new(VirtualClock::logical(tick))),
+		_ => None,
+	}
+}
+
+/// Wall clock time, as given by `Instant::now`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+	fn now(&self) -> Instant {
+		Instant::now()
+	}
+}
+
+/// Time that only moves forward when advanced, starting from the moment the clock is created.
+#[derive(Debug)]
+pub struct VirtualClock {
+	start: Instant,
+	/// Time elapsed since `start`, in nanoseconds.
+	elapsed: AtomicU64,
+	/// Time every request advances the clock by, in nanoseconds. 0 if only `advance` moves it.
+	tick: u64,
+}
+
+impl VirtualClock {
+	/// Creates a clock driven by the trace, see `advance` and `advance_to`.
+	pub fn new() -> Self {
+		Self::logical(Duration::ZERO)
+	}
+
+	/// Creates a logical clock that advances by `tick` on every request, so time is a request count.
+	///
+	/// E.g. with a tick of one second, an item put with a TTL of 10 seconds expires 10 requests later.
+	pub fn logical(tick: Duration) -> Self {
+		VirtualClock {
+			start: Instant::now(),
+			elapsed: AtomicU64::new(0),
+			tick: tick.as_nanos() as u64,
+		}
+	}
+
+	/// Moves the clock forward by `duration`.
+	pub fn advance(&self, duration: Duration) {
+		self.elapsed.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+	}
+
+	/// Moves the clock to `elapsed` after its creation, e.g. to the timestamp of a trace request.
+	///
+	/// The clock never goes back, an earlier time is ignored.
+	pub fn advance_to(&self, elapsed: Duration) {
+		self.elapsed.fetch_max(elapsed.as_nanos() as u64, Ordering::Relaxed);
+	}
+
+	/// Returns the time elapsed since the clock was created.
+	pub fn elapsed(&self) -> Duration {
+		Duration::from_nanos(self.elapsed.load(Ordering::Relaxed))
+	}
+}
+
+impl Default for VirtualClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Clock for VirtualClock {
+	fn now(&self) -> Instant {
+		self.start + self.elapsed()
+	}
+
+	fn on_request(&self) {
+		if self.tick > 0 {
+			self.elapsed.fetch_add(self.tick, Ordering::Relaxed);
+		}
+	}
+
+	fn advance_to(&self, elapsed: Duration) {
+		VirtualClock::advance_to(self, elapsed);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_virtual_clock_only_moves_forward_when_advanced() {
+		let clock = VirtualClock::new();
+		let start = clock.now();
+		clock.on_request();
+		assert_eq!(clock.now(), start);
+
+		clock.advance_to(Duration::from_secs(5));
+		clock.advance_to(Duration::from_secs(2));
+		clock.advance(Duration::from_secs(1));
+		assert_eq!(clock.now() - start, Duration::from_secs(6));
+
+		let logical = VirtualClock::logical(Duration::from_secs(1));
+		let start = logical.now();
+		for _ in 0..3 {
+			logical.on_request();
+		}
+		assert_eq!(logical.elapsed(), Duration::from_secs(3));
+		assert_eq!(logical.now() - start, Duration::from_secs(3));
+	}
+}
@@ -0,0 +1,290 @@
+//! A thread-safe variant of `Dash` for fixed-capacity caching.
+//!
+//! Every bucket carries a version lock as in the Dash paper: readers do not lock, they validate the
+//! bucket version after searching it and retry if a writer got in the way. Writers spin on the locks
+//! of the target, probing and stash buckets of the key, always in this order.
+//!
+//! Compared to `Dash`, the directory does not grow (only `DashMode::Cache` is supported), items are
+//! never moved between buckets, and keys and values are stored in atomic words (see `AtomicWord`).
+
+use crate::dash::dash_settings::{AdmissionPolicy, DashMode, DashSettings};
+use crate::eviction_policy::EvictionPolicy;
+use crate::hasher::DefaultBuildHasher;
+use crate::shared::hash_layout::HashLayout;
+use crate::shared::utils::hash;
+use atomic_word::AtomicWord;
+use concurrent_dash_bucket::MAX_BUCKET_SIZE;
+use concurrent_dash_segment::ConcurrentDashSegment;
+
+use log::info;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::hash::{BuildHasher, Hash};
+
+pub mod atomic_word;
+mod concurrent_dash_bucket;
+pub mod concurrent_dash_jni;
+mod concurrent_dash_segment;
+mod version_lock;
+
+/// Settings the concurrent variant does not support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsupportedSettings {
+	/** Only `DashMode::Cache` is supported, the directory never grows */
+	Mode(DashMode),
+	/** Admission filters are not supported */
+	AdmissionPolicy(AdmissionPolicy),
+	/** Buckets are limited to `MAX_BUCKET_SIZE` slots */
+	BucketSize(usize),
+	/** Only the policies that rank items with a single counter are supported */
+	EvictionPolicy(EvictionPolicy),
+}
+
+impl Display for UnsupportedSettings {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			UnsupportedSettings::Mode(mode) => write!(f, "unsupported mode {:?}, only the cache mode is supported", mode),
+			UnsupportedSettings::AdmissionPolicy(admission_policy) => {
+				write!(f, "unsupported admission policy {:?}", admission_policy)
+			}
+			UnsupportedSettings::BucketSize(bucket_size) => write!(
+				f,
+				"bucket size {} is over the limit of {} slots",
+				bucket_size, MAX_BUCKET_SIZE
+			),
+			UnsupportedSettings::EvictionPolicy(eviction_policy) => {
+				write!(f, "unsupported eviction policy {:?}", eviction_policy)
+			}
+		}
+	}
+}
+
+impl Error for UnsupportedSettings {}
+
+pub struct ConcurrentDash<K, V, S = DefaultBuildHasher> {
+	segments: Box<[ConcurrentDashSegment<K, V>]>,
+	layout: HashLayout,
+	hash_builder: S,
+}
+
+impl<K, V> ConcurrentDash<K, V>
+where
+	K: Hash + Eq + Copy + Debug + AtomicWord,
+	V: Eq + Copy + Debug + AtomicWord,
+{
+	/// Creates a new concurrent Dash instance with the given settings and the default hasher.
+	pub fn new(settings: DashSettings) -> Result<Self, UnsupportedSettings> {
+		Self::with_hasher(settings, DefaultBuildHasher::default())
+	}
+}
+
+impl<K, V, S> ConcurrentDash<K, V, S>
+where
+	K: Hash + Eq + Copy + Debug + AtomicWord,
+	V: Eq + Copy + Debug + AtomicWord,
+	S: BuildHasher + Clone + Debug,
+{
+	/// Creates a new concurrent Dash instance with the given settings, hashing keys with `hash_builder`.
+	///
+	/// Fails if the settings ask for something the concurrent variant does not support.
+	pub fn with_hasher(settings: DashSettings, hash_builder: S) -> Result<Self, UnsupportedSettings> {
+		info!("Creating a new concurrent Dash instance with settings: {:?}", settings);
+		if settings.mode != DashMode::Cache {
+			return Err(UnsupportedSettings::Mode(settings.mode));
+		}
+		if settings.admission_policy != AdmissionPolicy::AdmitAll {
+			return Err(UnsupportedSettings::AdmissionPolicy(settings.admission_policy));
+		}
+		if settings.bucket_size > MAX_BUCKET_SIZE {
+			return Err(UnsupportedSettings::BucketSize(settings.bucket_size));
+		}
+		if !Self::supports(&settings.eviction_policy) {
+			return Err(UnsupportedSettings::EvictionPolicy(settings.eviction_policy));
+		}
+
+		let layout = HashLayout::new(
+			settings.num_of_segments,
+			settings.num_of_normal_buckets,
+			settings.num_of_stash_buckets,
+		);
+		let segments = (0..settings.num_of_segments)
+			.map(|_| ConcurrentDashSegment::new(&settings, layout))
+			.collect();
+		Ok(Self {
+			segments,
+			layout,
+			hash_builder,
+		})
+	}
+
+	/// Returns whether the eviction policy can be ranked with a single counter per item.
+	pub fn supports(eviction_policy: &EvictionPolicy) -> bool {
+		matches!(
+			eviction_policy,
+			EvictionPolicy::ClassicLRU
+				| EvictionPolicy::TimestampLRU
+				| EvictionPolicy::Lifo
+				| EvictionPolicy::Lfu
+				| EvictionPolicy::Fifo
+		)
+	}
+
+	/// Insert a key-value pair, or replace the value if the key already exists.
+	///
+	/// Returns the previous value of the key, if any, and the key-value pair evicted to make room for the new one, if any.
+	pub fn put(&self, key: K, value: V) -> (Option<V>, Option<(K, V)>) {
+		let hash = hash(&key, &self.hash_builder);
+		self.get_segment(hash).put(key, value, hash)
+	}
+
+	/// Returns the value of key if exists wrapper in Some ans None otherwise
+	///
+	/// As a side effect makes updates according to the eviction policy.
+	pub fn get_and_update_item(&self, key: &K) -> Option<V> {
+		let hash = hash(key, &self.hash_builder);
+		self.get_segment(hash).get(key, hash)
+	}
+
+	/// Removes the key and returns its value if it was present.
+	pub fn remove(&self, key: &K) -> Option<V> {
+		let hash = hash(key, &self.hash_builder);
+		self.get_segment(hash).remove(key, hash)
+	}
+
+	fn get_segment(&self, hash: u64) -> &ConcurrentDashSegment<K, V> {
+		&self.segments[self.layout.segment_index(hash)]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+	use std::thread;
+
+	fn assert_sync<T: Sync + Send>() {}
+
+	#[test]
+	fn test_is_sync() {
+		assert_sync::<ConcurrentDash<i64, i64>>();
+	}
+
+	#[test]
+	fn test_put_get_remove() {
+		let dash: ConcurrentDash<i64, i64> = ConcurrentDash::new(DashSettings::default()).unwrap();
+		assert_eq!(dash.put(1, 10), (None, None));
+		assert_eq!(dash.put(1, 11), (Some(10), None));
+		assert_eq!(dash.get_and_update_item(&1), Some(11));
+		assert_eq!(dash.remove(&1), Some(11));
+		assert_eq!(dash.get_and_update_item(&1), None);
+	}
+
+	#[test]
+	fn test_rejects_unsupported_settings() {
+		let settings = DashSettings {
+			eviction_policy: EvictionPolicy::Arc,
+			..DashSettings::default()
+		};
+		assert_eq!(
+			ConcurrentDash::<i64, i64>::new(settings).err(),
+			Some(UnsupportedSettings::EvictionPolicy(EvictionPolicy::Arc))
+		);
+		let settings = DashSettings {
+			mode: DashMode::Table,
+			..DashSettings::default()
+		};
+		assert_eq!(
+			ConcurrentDash::<i64, i64>::new(settings).err(),
+			Some(UnsupportedSettings::Mode(DashMode::Table))
+		);
+	}
+
+	#[test]
+	fn test_concurrent_writers_and_readers() {
+		let dash: Arc<ConcurrentDash<i64, i64>> = Arc::new(
+			ConcurrentDash::new(DashSettings {
+				num_of_segments: 4,
+				eviction_policy: EvictionPolicy::Lfu,
+				..DashSettings::default()
+			})
+			.unwrap(),
+		);
+		let threads: Vec<_> = (0..4_i64)
+			.map(|thread_index| {
+				let dash = Arc::clone(&dash);
+				thread::spawn(move || {
+					let mut evicted = 0;
+					for round in 0..2000_i64 {
+						let key = (round * 7 + thread_index) % 3000;
+						// Every value written encodes its key, so a torn read would be noticed
+						if dash.put(key, key * 1000 + thread_index).1.is_some() {
+							evicted += 1;
+						}
+						if let Some(value) = dash.get_and_update_item(&((round * 13) % 3000)) {
+							assert_eq!(value / 1000, (round * 13) % 3000);
+						}
+					}
+					evicted
+				})
+			})
+			.collect();
+		let evicted: usize = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+
+		let cached = (0..3000).filter(|key| dash.get_and_update_item(key).is_some()).count();
+		assert!(cached > 0);
+		assert!(cached <= 4 * (28 + 4) * 16);
+		assert!(evicted > 0);
+	}
+
+	#[test]
+	fn test_contended_buckets_never_mix_keys_and_values() {
+		for eviction_policy in [EvictionPolicy::ClassicLRU, EvictionPolicy::Lfu] {
+			// A handful of small buckets, so readers, hits and writers keep running into each other
+			let dash: Arc<ConcurrentDash<i64, i64>> = Arc::new(
+				ConcurrentDash::new(DashSettings {
+					num_of_segments: 1,
+					num_of_normal_buckets: 2,
+					num_of_stash_buckets: 1,
+					bucket_size: 4,
+					eviction_policy: eviction_policy.clone(),
+					..DashSettings::default()
+				})
+				.unwrap(),
+			);
+			let threads: Vec<_> = (0..8_i64)
+				.map(|thread_index| {
+					let dash = Arc::clone(&dash);
+					thread::spawn(move || {
+						for round in 0..20_000_i64 {
+							let key = (round * 31 + thread_index * 7) % 24;
+							match round % 4 {
+								0 => {
+									if let (_, Some((evicted_key, evicted_value))) = dash.put(key, key * 1000 + thread_index) {
+										assert_eq!(evicted_value / 1000, evicted_key);
+									}
+								}
+								1 => {
+									dash.remove(&key);
+								}
+								_ => {
+									if let Some(value) = dash.get_and_update_item(&key) {
+										assert_eq!(value / 1000, key);
+									}
+								}
+							}
+						}
+					})
+				})
+				.collect();
+			for thread in threads {
+				thread.join().unwrap();
+			}
+
+			for key in 0..24 {
+				if let Some(value) = dash.get_and_update_item(&key) {
+					assert_eq!(value / 1000, key, "{:?}", eviction_policy);
+				}
+			}
+		}
+	}
+}
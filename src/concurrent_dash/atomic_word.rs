@@ -0,0 +1,41 @@
+/// A plain value that fits in a 64-bit word, so the concurrent buckets can store it in an `AtomicU64`.
+///
+/// Optimistic readers load the words of an item while a writer may be storing them. Atomic words never
+/// tear and any word converts back to a valid value, so such a read is only discarded, never undefined.
+pub trait AtomicWord: Copy {
+	fn into_word(self) -> u64;
+
+	fn from_word(word: u64) -> Self;
+}
+
+macro_rules! impl_atomic_word {
+	($($ty:ty),*) => {
+		$(
+			impl AtomicWord for $ty {
+				fn into_word(self) -> u64 {
+					self as u64
+				}
+
+				fn from_word(word: u64) -> Self {
+					word as $ty
+				}
+			}
+		)*
+	};
+}
+
+impl_atomic_word!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		for value in [i64::MIN, -1, 0, 1, i64::MAX] {
+			assert_eq!(i64::from_word(value.into_word()), value);
+		}
+		assert_eq!(i32::from_word((-7_i32).into_word()), -7);
+		assert_eq!(u8::from_word(u8::MAX.into_word()), u8::MAX);
+	}
+}
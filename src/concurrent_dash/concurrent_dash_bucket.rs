@@ -0,0 +1,219 @@
+use super::atomic_word::AtomicWord;
+use super::version_lock::{VersionLock, VersionLockGuard};
+use crate::eviction_policy::EvictionPolicy;
+
+use std::cmp::Ordering as CmpOrdering;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Maximum number of slots of a bucket, one bit of the occupancy bitmap per slot.
+pub const MAX_BUCKET_SIZE: usize = u64::BITS as usize;
+
+struct Slot {
+	/// Key and value of the item, see `AtomicWord`.
+	key: AtomicU64,
+	value: AtomicU64,
+	/// Meaning depends on the eviction policy: last access or insertion tick, or access count.
+	rank: AtomicU64,
+}
+
+/// A fixed-capacity bucket guarded by a `VersionLock`.
+///
+/// The slots are allocated once and never move, and every word of an item is an atomic, so an optimistic
+/// reader racing with a writer may read a key and a value from different writes but never a torn word.
+/// Such a reader only trusts what it read once the version is validated.
+pub struct ConcurrentDashBucket<K, V> {
+	lock: VersionLock,
+	/// Bit `i` is set when `slots[i]` holds an item.
+	occupied: AtomicU64,
+	fingerprints: Box<[AtomicU8]>,
+	slots: Box<[Slot]>,
+	_items: PhantomData<(K, V)>,
+}
+
+impl<K, V> ConcurrentDashBucket<K, V>
+where
+	K: Eq + AtomicWord,
+	V: AtomicWord,
+{
+	pub fn new(max_size: usize) -> Self {
+		assert!(
+			max_size <= MAX_BUCKET_SIZE,
+			"bucket size is limited to {} slots",
+			MAX_BUCKET_SIZE
+		);
+		ConcurrentDashBucket {
+			lock: VersionLock::new(),
+			occupied: AtomicU64::new(0),
+			fingerprints: (0..max_size).map(|_| AtomicU8::new(0)).collect(),
+			slots: (0..max_size)
+				.map(|_| Slot {
+					key: AtomicU64::new(0),
+					value: AtomicU64::new(0),
+					rank: AtomicU64::new(0),
+				})
+				.collect(),
+			_items: PhantomData,
+		}
+	}
+
+	/// Looks the key up without taking the lock, retrying while writers get in the way.
+	///
+	/// Returns the slot of the key and its value.
+	pub fn find(&self, key: &K, fingerprint: u8) -> Option<(usize, V)> {
+		'retry: loop {
+			let version = self.lock.read_begin();
+			for slot in self.candidate_slots(fingerprint) {
+				let (slot_key, slot_value) = self.read(slot);
+				if !self.lock.read_validate(version) {
+					continue 'retry;
+				}
+				// No writer got in the way, so the key and the value belong to the same item
+				if slot_key == *key {
+					return Some((slot, slot_value));
+				}
+			}
+			if self.lock.read_validate(version) {
+				return None;
+			}
+		}
+	}
+
+	/// Updates the rank of the item with `key` in `slot` after a hit, under the lock.
+	///
+	/// Nothing is updated if the slot was reused by another key since the lookup.
+	pub fn touch(&self, slot: usize, key: &K, eviction_policy: &EvictionPolicy, tick: u64) {
+		let guard = self.lock();
+		if guard.is_occupied(slot) && guard.read(slot).0 == *key {
+			guard.touch(slot, eviction_policy, tick);
+		}
+	}
+
+	/// Takes the write lock of the bucket.
+	pub fn lock(&self) -> ConcurrentDashBucketGuard<'_, K, V> {
+		ConcurrentDashBucketGuard {
+			_guard: self.lock.lock(),
+			bucket: self,
+		}
+	}
+
+	/// Returns the occupied slots whose fingerprint matches.
+	fn candidate_slots(&self, fingerprint: u8) -> impl Iterator<Item = usize> + '_ {
+		let occupied = self.occupied.load(Ordering::Acquire);
+		(0..self.slots.len())
+			.filter(move |&slot| occupied & (1 << slot) != 0)
+			.filter(move |&slot| self.fingerprints[slot].load(Ordering::Relaxed) == fingerprint)
+	}
+
+	/// Loads the key and the value in `slot`, which only belong to the same item if no writer got in the way.
+	fn read(&self, slot: usize) -> (K, V) {
+		let slot = &self.slots[slot];
+		(
+			K::from_word(slot.key.load(Ordering::Relaxed)),
+			V::from_word(slot.value.load(Ordering::Relaxed)),
+		)
+	}
+}
+
+/// Write access to a `ConcurrentDashBucket`, the lock is released when the guard is dropped.
+pub struct ConcurrentDashBucketGuard<'a, K, V> {
+	_guard: VersionLockGuard<'a>,
+	bucket: &'a ConcurrentDashBucket<K, V>,
+}
+
+impl<K, V> ConcurrentDashBucketGuard<'_, K, V>
+where
+	K: Eq + AtomicWord,
+	V: AtomicWord,
+{
+	/// Returns the slot of the key, or `None` if the key is not in the bucket.
+	pub fn position(&self, key: &K, fingerprint: u8) -> Option<usize> {
+		self
+			.bucket
+			.candidate_slots(fingerprint)
+			.find(|&slot| self.read(slot).0 == *key)
+	}
+
+	pub fn is_occupied(&self, slot: usize) -> bool {
+		self.bucket.occupied.load(Ordering::Relaxed) & (1 << slot) != 0
+	}
+
+	pub fn size(&self) -> usize {
+		self.bucket.occupied.load(Ordering::Relaxed).count_ones() as usize
+	}
+
+	/// Puts the item into a free slot and returns the slot, or `None` if the bucket is full.
+	pub fn insert(&mut self, key: K, value: V, fingerprint: u8, rank: u64) -> Option<usize> {
+		let occupied = self.bucket.occupied.load(Ordering::Relaxed);
+		let slot = (!occupied).trailing_zeros() as usize;
+		if slot >= self.bucket.slots.len() {
+			return None;
+		}
+		self.write(slot, key, value);
+		self.bucket.slots[slot].rank.store(rank, Ordering::Relaxed);
+		self.bucket.fingerprints[slot].store(fingerprint, Ordering::Relaxed);
+		self.bucket.occupied.store(occupied | (1 << slot), Ordering::Release);
+		Some(slot)
+	}
+
+	/// Replaces the value of the item in `slot` and returns the previous value.
+	pub fn replace_value(&mut self, slot: usize, value: V) -> V {
+		let (key, previous_value) = self.read(slot);
+		self.write(slot, key, value);
+		previous_value
+	}
+
+	/// Removes the item in `slot` and returns it.
+	pub fn remove(&mut self, slot: usize) -> (K, V) {
+		let entry = self.read(slot);
+		let occupied = self.bucket.occupied.load(Ordering::Relaxed);
+		self.bucket.occupied.store(occupied & !(1 << slot), Ordering::Release);
+		entry
+	}
+
+	/// Updates the rank of the item in `slot` after a hit.
+	pub fn touch(&self, slot: usize, eviction_policy: &EvictionPolicy, tick: u64) {
+		let rank = &self.bucket.slots[slot].rank;
+		match eviction_policy {
+			EvictionPolicy::ClassicLRU | EvictionPolicy::TimestampLRU => rank.store(tick, Ordering::Relaxed),
+			EvictionPolicy::Lfu => {
+				rank.fetch_add(1, Ordering::Relaxed);
+			}
+			// The other policies are rejected by `ConcurrentDash::with_hasher`
+			_ => {}
+		}
+	}
+
+	/// Returns the slot the eviction policy would evict next and its rank, or `None` if the bucket is empty.
+	pub fn get_victim(&self, eviction_policy: &EvictionPolicy) -> Option<(usize, u64)> {
+		let occupied = self.bucket.occupied.load(Ordering::Relaxed);
+		let ranks = (0..self.bucket.slots.len())
+			.filter(|&slot| occupied & (1 << slot) != 0)
+			.map(|slot| (slot, self.bucket.slots[slot].rank.load(Ordering::Relaxed)));
+		match eviction_policy {
+			EvictionPolicy::Lifo => ranks.max_by_key(|&(_, rank)| rank),
+			_ => ranks.min_by_key(|&(_, rank)| rank),
+		}
+	}
+
+	fn read(&self, slot: usize) -> (K, V) {
+		self.bucket.read(slot)
+	}
+
+	fn write(&mut self, slot: usize, key: K, value: V) {
+		// Concurrent optimistic readers discard what they read, the version changes when the lock is released
+		let slot = &self.bucket.slots[slot];
+		slot.key.store(key.into_word(), Ordering::Relaxed);
+		slot.value.store(value.into_word(), Ordering::Relaxed);
+	}
+}
+
+/// Compares the ranks of two eviction candidates.
+///
+/// Returns `Ordering::Less` if the item ranked `a` should be evicted before the one ranked `b`.
+pub fn compare_ranks(eviction_policy: &EvictionPolicy, a: u64, b: u64) -> CmpOrdering {
+	match eviction_policy {
+		EvictionPolicy::Lifo => b.cmp(&a),
+		_ => a.cmp(&b),
+	}
+}
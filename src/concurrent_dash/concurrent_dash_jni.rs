@@ -0,0 +1,120 @@
+use super::ConcurrentDash;
+use crate::dash::dash_jni::init_logger;
+use crate::dash::dash_settings::DashSettings;
+use crate::eviction_policy::EvictionPolicy;
+use crate::hasher::HasherKind;
+
+use jni::{
+	objects::JClass,
+	sys::{jboolean, jlong},
+	JNIEnv,
+};
+use log::{error, info};
+
+type ConcurrentDashTy = ConcurrentDash<i64, i64, HasherKind>;
+
+// The cache pointer is shared between Java threads, so it is only ever dereferenced as a shared reference.
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_ConcurrentDashRustPolicy_initCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	num_of_segments: jlong,
+	num_of_normal_buckets: jlong,
+	num_of_stash_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	debug_mode: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+) -> jlong {
+	init_logger(debug_mode as usize);
+	// Unknown ids and unsupported settings are refused with a null pointer instead of unwinding into the JVM
+	let Some(eviction_policy) = EvictionPolicy::from_usize(eviction_policy as usize) else {
+		error!("new - unknown eviction policy {}", eviction_policy);
+		return 0;
+	};
+	let Some(hash_builder) = HasherKind::from_usize(hasher as usize, hasher_seed as u64) else {
+		error!("new - unknown hasher {}", hasher);
+		return 0;
+	};
+	let settings = DashSettings {
+		num_of_segments: num_of_segments as usize,
+		num_of_normal_buckets: num_of_normal_buckets as usize,
+		num_of_stash_buckets: num_of_stash_buckets as usize,
+		bucket_size: bucket_size as usize,
+		eviction_policy,
+		debug_mode: debug_mode as usize,
+		..DashSettings::default()
+	};
+
+	let cache: ConcurrentDashTy = match ConcurrentDash::with_hasher(settings.clone(), hash_builder) {
+		Ok(cache) => cache,
+		Err(unsupported) => {
+			error!("new - {}", unsupported);
+			return 0;
+		}
+	};
+	let cache_ptr = Box::into_raw(Box::new(cache)) as jlong;
+
+	info!(
+		"new - Concurrent Cache Ptr: {}, initializing cache. Settings: {:?}, Hasher: {:?}",
+		cache_ptr, settings, hash_builder
+	);
+	cache_ptr
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_ConcurrentDashRustPolicy_getFromCacheIfPresent<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+) -> jlong {
+	let cache = unsafe { &*(cache_ptr as *const ConcurrentDashTy) };
+	cache.get_and_update_item(&key).unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_ConcurrentDashRustPolicy_putToCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+) -> jboolean {
+	let cache = unsafe { &*(cache_ptr as *const ConcurrentDashTy) };
+	let (previous_value, _) = cache.put(key, value);
+	previous_value.is_some() as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_ConcurrentDashRustPolicy_removeFromCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+) -> jlong {
+	let cache = unsafe { &*(cache_ptr as *const ConcurrentDashTy) };
+	cache.remove(&key).unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_ConcurrentDashRustPolicy_dropCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+) {
+	let _boxed_cache = unsafe { Box::from_raw(cache_ptr as *mut ConcurrentDashTy) };
+	info!("drop_cache - Concurrent Cache Ptr: {}", cache_ptr);
+}
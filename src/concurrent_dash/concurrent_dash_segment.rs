@@ -0,0 +1,122 @@
+use super::atomic_word::AtomicWord;
+use super::concurrent_dash_bucket::{compare_ranks, ConcurrentDashBucket, ConcurrentDashBucketGuard};
+use crate::dash::dash_settings::DashSettings;
+use crate::eviction_policy::EvictionPolicy;
+use crate::shared::hash_layout::HashLayout;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub struct ConcurrentDashSegment<K, V> {
+	buckets: Box<[ConcurrentDashBucket<K, V>]>,
+	stash_buckets: Box<[ConcurrentDashBucket<K, V>]>,
+	layout: HashLayout,
+	eviction_policy: EvictionPolicy,
+	/// Logical time of the segment, used to rank items by recency or insertion order.
+	clock: AtomicU64,
+}
+
+impl<K, V> ConcurrentDashSegment<K, V>
+where
+	K: Eq + AtomicWord,
+	V: AtomicWord,
+{
+	pub fn new(settings: &DashSettings, layout: HashLayout) -> Self {
+		ConcurrentDashSegment {
+			buckets: (0..settings.num_of_normal_buckets)
+				.map(|_| ConcurrentDashBucket::new(settings.bucket_size))
+				.collect(),
+			stash_buckets: (0..settings.num_of_stash_buckets)
+				.map(|_| ConcurrentDashBucket::new(settings.bucket_size))
+				.collect(),
+			layout,
+			eviction_policy: settings.eviction_policy.clone(),
+			clock: AtomicU64::new(0),
+		}
+	}
+
+	/// Returns the value of the key, looking at the target, probing and stash buckets without locking.
+	/// As a side effect makes updates according to the eviction policy, under the lock of the bucket of the key.
+	/// As a side effect makes updates according to the eviction policy.
+	pub fn get(&self, key: &K, hash: u64) -> Option<V> {
+		let fingerprint = HashLayout::fingerprint(hash);
+		self.candidate_buckets(hash).find_map(|bucket| {
+			let (slot, value) = bucket.find(key, fingerprint)?;
+			bucket.touch(slot, key, &self.eviction_policy, self.tick());
+			Some(value)
+		})
+	}
+
+	/// Inserts the key-value pair, or replaces the value if the key already exists.
+	///
+	/// The target, probing and stash buckets are locked in this order, so writers never deadlock.
+	/// The item goes to the less loaded of the target and probing buckets, then to the stash bucket.
+	/// If all three are full, the victim is selected across them according to the eviction policy.
+	///
+	/// Returns the previous value of the key, if any, and the evicted key-value pair, if any.
+	pub fn put(&self, key: K, value: V, hash: u64) -> (Option<V>, Option<(K, V)>) {
+		let fingerprint = HashLayout::fingerprint(hash);
+		let mut guards: Vec<ConcurrentDashBucketGuard<'_, K, V>> =
+			self.candidate_buckets(hash).map(|bucket| bucket.lock()).collect();
+		let tick = self.tick();
+
+		for guard in guards.iter_mut() {
+			if let Some(slot) = guard.position(&key, fingerprint) {
+				guard.touch(slot, &self.eviction_policy, tick);
+				return (Some(guard.replace_value(slot, value)), None);
+			}
+		}
+
+		let rank = match self.eviction_policy {
+			EvictionPolicy::Lfu => 0,
+			_ => tick,
+		};
+		// The stash is always the last guard, the normal buckets come before it
+		let (stash_guard, normal_guards) = guards.split_last_mut().unwrap();
+		let less_loaded_guard = normal_guards.iter_mut().min_by_key(|guard| guard.size()).unwrap();
+		if less_loaded_guard.insert(key, value, fingerprint, rank).is_some()
+			|| stash_guard.insert(key, value, fingerprint, rank).is_some()
+		{
+			return (None, None);
+		}
+
+		let victim_guard = guards
+			.iter_mut()
+			.filter_map(|guard| Some((guard.get_victim(&self.eviction_policy)?, guard)))
+			.min_by(|((_, a), _), ((_, b), _)| compare_ranks(&self.eviction_policy, *a, *b))
+			.map(|((slot, _), guard)| (slot, guard));
+		match victim_guard {
+			Some((slot, guard)) => {
+				let evicted = guard.remove(slot);
+				guard.insert(key, value, fingerprint, rank);
+				(None, Some(evicted))
+			}
+			// Zero-sized buckets, nothing can be stored
+			None => (None, None),
+		}
+	}
+
+	/// Removes the key and returns its value if it was present.
+	pub fn remove(&self, key: &K, hash: u64) -> Option<V> {
+		let fingerprint = HashLayout::fingerprint(hash);
+		self.candidate_buckets(hash).find_map(|bucket| {
+			let mut guard = bucket.lock();
+			let slot = guard.position(key, fingerprint)?;
+			Some(guard.remove(slot).1)
+		})
+	}
+
+	/// Returns the target bucket, the probing bucket if there is one, and the stash bucket, in locking order.
+	fn candidate_buckets(&self, hash: u64) -> impl Iterator<Item = &ConcurrentDashBucket<K, V>> {
+		let target_bucket_index = self.layout.bucket_index(hash);
+		let probing_bucket = self.buckets.get(target_bucket_index + 1);
+		std::iter::once(&self.buckets[target_bucket_index])
+			.chain(probing_bucket)
+			.chain(std::iter::once(
+				&self.stash_buckets[self.layout.stash_bucket_index(hash)],
+			))
+	}
+
+	fn tick(&self) -> u64 {
+		self.clock.fetch_add(1, Ordering::Relaxed)
+	}
+}
@@ -0,0 +1,85 @@
+use std::hint::spin_loop;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+/// A version counter whose lowest bit is a write lock, as used by the Dash paper for every bucket.
+///
+/// Writers take the lock by making the version odd and release it by making it even again,
+/// so readers can run without locking and validate afterwards that no writer got in the way.
+#[derive(Debug, Default)]
+pub struct VersionLock {
+	version: AtomicU64,
+}
+
+impl VersionLock {
+	pub fn new() -> Self {
+		VersionLock {
+			version: AtomicU64::new(0),
+		}
+	}
+
+	/// Starts an optimistic read, waiting for a running writer to finish.
+	///
+	/// Returns the version to pass to `read_validate`.
+	pub fn read_begin(&self) -> u64 {
+		loop {
+			let version = self.version.load(Ordering::Acquire);
+			if version & 1 == 0 {
+				return version;
+			}
+			spin_loop();
+		}
+	}
+
+	/// Returns whether nothing was written since `read_begin` returned `version`.
+	pub fn read_validate(&self, version: u64) -> bool {
+		// The reads of the protected data must not be reordered after the version check
+		fence(Ordering::Acquire);
+		self.version.load(Ordering::Relaxed) == version
+	}
+
+	/// Spins until the write lock is taken.
+	pub fn lock(&self) -> VersionLockGuard<'_> {
+		loop {
+			let version = self.version.load(Ordering::Relaxed);
+			if version & 1 == 0
+				&& self
+					.version
+					.compare_exchange_weak(version, version + 1, Ordering::Acquire, Ordering::Relaxed)
+					.is_ok()
+			{
+				// Readers must see the odd version before any of the writes that follow
+				fence(Ordering::Release);
+				return VersionLockGuard { lock: self };
+			}
+			spin_loop();
+		}
+	}
+}
+
+/// Releases the write lock, bumping the version, when dropped.
+#[derive(Debug)]
+pub struct VersionLockGuard<'a> {
+	lock: &'a VersionLock,
+}
+
+impl Drop for VersionLockGuard<'_> {
+	fn drop(&mut self) {
+		self.lock.version.fetch_add(1, Ordering::Release);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_write_invalidates_read() {
+		let lock = VersionLock::new();
+		let version = lock.read_begin();
+		assert!(lock.read_validate(version));
+
+		drop(lock.lock());
+		assert!(!lock.read_validate(version));
+		assert!(lock.read_validate(lock.read_begin()));
+	}
+}
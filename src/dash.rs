@@ -1,24 +1,93 @@
-use std::fmt::{Debug, Display, Formatter};
-use std::hash::Hash;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::{BuildHasher, Hash};
+use std::time::Duration;
 
+use crate::clock::{real_clock, SharedClock};
+use crate::eviction_policy::EvictionPolicy;
+use crate::hasher::DefaultBuildHasher;
+use crate::shared::admission_window::AdmissionWindow;
+use crate::shared::hash_layout::HashLayout;
+use crate::shared::hit_ratio::HitRatio;
 use crate::shared::item::Item;
-use crate::shared::utils::get_index;
+use crate::shared::tiny_lfu::TinyLfu;
+use crate::shared::traits::bucket::Bucket;
+use crate::shared::utils::{hash, into_pairs};
+use dash_sampling::EvictionPool;
 use dash_segment::DashSegment;
-use dash_settings::DashSettings;
+use dash_settings::{AdmissionPolicy, DashMode, DashSettings, EvictionScope, InsertMode};
+use dash_stats::BucketLoadSkew;
 use log::{debug, info};
 
+mod dash_arc;
 mod dash_bucket;
 pub mod dash_jni;
+mod dash_sampling;
 mod dash_segment;
 pub mod dash_settings;
+pub mod dash_stats;
+
+/// Upper bound for the directory depth, after which a full segment falls back to eviction.
+const MAX_GLOBAL_DEPTH: usize = HashLayout::DIRECTORY_BITS as usize;
+
+/// Combinations of settings Dash does not support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsupportedSettings {
+	/** S3-FIFO and ARC do not support a bucket capacity in bytes */
+	BucketCapacityBytes(usize),
+	/** ARC adapts its target per segment and does not support sampled eviction */
+	EvictionScope(EvictionScope),
+}
+
+impl Display for UnsupportedSettings {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			UnsupportedSettings::BucketCapacityBytes(bucket_capacity_bytes) => write!(
+				f,
+				"bucket capacity of {} bytes is not supported by S3-FIFO and ARC",
+				bucket_capacity_bytes
+			),
+			UnsupportedSettings::EvictionScope(eviction_scope) => {
+				write!(f, "unsupported eviction scope {:?} with ARC", eviction_scope)
+			}
+		}
+	}
+}
+
+impl Error for UnsupportedSettings {}
 
 #[derive(Debug)]
-pub struct Dash<K, V>
+pub struct Dash<K, V, S = DefaultBuildHasher>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
-	pub segments: Vec<DashSegment<K, V>>,
+	pub segments: Vec<DashSegment<K, V, S>>,
+	/// Maps the `global_depth` most significant hash bits to an index in `segments`.
+	/// Only used in `DashMode::Table`.
+	pub directory: Vec<usize>,
+	pub global_depth: usize,
+	layout: HashLayout,
+	mode: DashMode,
+	/// Set with `AdmissionPolicy::TinyLfu` and `WTinyLfu`, records every access and filters the keys put into a full segment.
+	admission_filter: Option<TinyLfu>,
+	/// Set with `AdmissionPolicy::WTinyLfu` in `DashMode::Cache`, holds the new keys until they have to be admitted.
+	admission_window: Option<AdmissionWindow<K, V>>,
+	/// Set with `EvictionScope::SampledSharedPool` in `DashMode::Cache`, the eviction candidates of every segment.
+	eviction_pool: Option<EvictionPool<K>>,
+	/// Hits and misses of `get_and_update_item`, see `hit_ratio`.
+	hit_ratio: HitRatio,
+	/// Time to live of the items put without one, `None` if they never expire.
+	default_ttl: Option<Duration>,
+	/// Whether an item with an expiration time was ever put, expired items are only reclaimed once it was.
+	expiring: bool,
+	/// Number of buckets the sweeper checks for expired items on every put, 0 disables it.
+	expiration_sweep_buckets: usize,
+	/// Segment and bucket index the sweeper continues from, stash buckets come after the normal ones.
+	sweep_cursor: (usize, usize),
+	clock: SharedClock,
+	hash_builder: S,
 }
 
 impl<K, V> Dash<K, V>
@@ -26,23 +95,207 @@ where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
 {
-	/// Creates a new Dash instance with the given settings.
-	pub fn new(settings: DashSettings) -> Self {
+	/// Creates a new Dash instance with the given settings and the default hasher.
+	pub fn new(settings: DashSettings) -> Result<Self, UnsupportedSettings> {
+		Self::with_hasher(settings, DefaultBuildHasher::default())
+	}
+}
+
+impl<K, V, S> Dash<K, V, S>
+where
+	K: Hash + Eq + Copy + Debug,
+	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
+{
+	/// Creates a new Dash instance with the given settings, hashing keys with `hash_builder`.
+	///
+	/// In `DashMode::Table` the number of segments is rounded up to a power of two.
+	/// Fails if the settings combine features that do not work together.
+	pub fn with_hasher(settings: DashSettings, hash_builder: S) -> Result<Self, UnsupportedSettings> {
+		Self::with_clock(settings, hash_builder, real_clock())
+	}
+
+	/// Like `with_hasher`, taking the time from `clock` rather than from the wall clock.
+	///
+	/// With a `VirtualClock` two runs of the same trace make the same eviction decisions.
+	pub fn with_clock(settings: DashSettings, hash_builder: S, clock: SharedClock) -> Result<Self, UnsupportedSettings> {
 		info!("Creating a new Dash instance with settings: {:?}", settings);
+		let is_arc = settings.eviction_policy == EvictionPolicy::Arc;
+		if let Some(bucket_capacity_bytes) = settings.bucket_capacity_bytes {
+			if settings.insert_mode == InsertMode::S3Fifo || is_arc {
+				return Err(UnsupportedSettings::BucketCapacityBytes(bucket_capacity_bytes));
+			}
+		}
+		if is_arc && !matches!(settings.eviction_scope, EvictionScope::Bucket | EvictionScope::Segment) {
+			return Err(UnsupportedSettings::EvictionScope(settings.eviction_scope));
+		}
+
+		let num_of_segments = match settings.mode {
+			DashMode::Cache => settings.num_of_segments,
+			DashMode::Table => settings.num_of_segments.next_power_of_two(),
+		};
+		let global_depth = match settings.mode {
+			DashMode::Cache => 0,
+			DashMode::Table => num_of_segments.trailing_zeros() as usize,
+		};
+
+		let num_of_buckets = settings.num_of_normal_buckets + settings.num_of_stash_buckets;
+		let capacity = num_of_segments * num_of_buckets * settings.bucket_size;
+		let admission_filter = match settings.admission_policy {
+			AdmissionPolicy::AdmitAll => None,
+			AdmissionPolicy::TinyLfu | AdmissionPolicy::WTinyLfu => Some(TinyLfu::new(capacity)),
+		};
+		// A table admits every key, so it needs no window
+		let admission_window = (settings.admission_policy == AdmissionPolicy::WTinyLfu && settings.mode == DashMode::Cache)
+			.then(|| AdmissionWindow::new(capacity));
+
+		let eviction_pool = match (settings.mode, settings.eviction_scope) {
+			(DashMode::Cache, EvictionScope::SampledSharedPool) => Some(EvictionPool::new(
+				settings.eviction_samples,
+				settings.eviction_pool_size,
+				settings.sampling_seed,
+			)),
+			_ => None,
+		};
+
 		// TODO: think about maybe using Vec::with_capacity
 		let mut segments = Vec::new();
-		for _ in 0..settings.num_of_segments {
+		for _ in 0..num_of_segments {
 			// TODO: pass the settings as a reference
-			segments.push(DashSegment::new(settings.clone()));
+			segments.push(DashSegment::new(settings.clone(), global_depth, hash_builder.clone()).with_clock(clock.clone()));
 		}
-		Self { segments }
+		Ok(Self {
+			segments,
+			directory: (0..num_of_segments).collect(),
+			global_depth,
+			layout: HashLayout::new(
+				num_of_segments,
+				settings.num_of_normal_buckets,
+				settings.num_of_stash_buckets,
+			),
+			mode: settings.mode,
+			admission_filter,
+			admission_window,
+			eviction_pool,
+			hit_ratio: HitRatio::default(),
+			default_ttl: settings.default_ttl,
+			expiring: settings.default_ttl.is_some(),
+			expiration_sweep_buckets: settings.expiration_sweep_buckets,
+			sweep_cursor: (0, 0),
+			clock,
+			hash_builder,
+		})
+	}
+
+	/// Insert a key-value pair into Dash, or replace the value if the key already exists.
+	///
+	/// In `DashMode::Table` a full segment is split (doubling the directory if needed) instead of evicting.
+	///
+	/// Returns the previous value of the key, if any, and the key-value pairs evicted to make room for the new one,
+	/// in eviction order. A capacity in bytes may take several of them to fit a large item.
+	/// If the admission filter rejects a new key, the key-value pair itself is returned as evicted.
+	/// With `AdmissionPolicy::WTinyLfu` a new key goes into the window, and the item the window pushes out
+	/// is the one admitted into the buckets or returned as evicted.
+	/// As a side effect, replacing a value makes updates according to the eviction policy.
+	pub fn put(&mut self, key: K, value: V) -> (Option<V>, Vec<(K, V)>) {
+		self.put_item(key, value, |item| item)
+	}
+
+	/// Like `put`, for an item taking `size` bytes of the capacity of its bucket.
+	///
+	/// The size only matters with `DashSettings::bucket_capacity_bytes` or a size-aware eviction policy.
+	/// An existing key takes the new size and is put again like a new key if it no longer fits in its bucket.
+	/// A plain `put` gives it the size of an item without one again.
+	pub fn put_with_size(&mut self, key: K, value: V, size: usize) -> (Option<V>, Vec<(K, V)>) {
+		self.put_item(key, value, |item| item.with_size(size))
+	}
+
+	/// Like `put`, for an item that expires `ttl` from now.
+	///
+	/// An expired item is never returned and its slot is reclaimed before any live item is evicted.
+	/// Replacing the value of an existing key also replaces its expiration time.
+	pub fn put_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> (Option<V>, Vec<(K, V)>) {
+		self.put_item(key, value, |item| item.with_ttl(ttl))
+	}
+
+	/// Moves a virtual clock to `elapsed` after its creation, e.g. to the timestamp of a trace request.
+	///
+	/// Does nothing with the wall clock. A virtual clock never goes back, an earlier time is ignored.
+	pub fn advance_clock(&self, elapsed: Duration) {
+		self.clock.advance_to(elapsed);
 	}
 
-	/// Insert a key-value pair into Dash
-	pub fn put(&mut self, key: K, value: V) {
+	/// Puts the item `build` makes out of a plain item created at the current time of the clock.
+	fn put_item(&mut self, key: K, value: V, build: impl FnOnce(Item<K, V>) -> Item<K, V>) -> (Option<V>, Vec<(K, V)>) {
 		debug!("Dash: {:?}", self);
-		let segment = self.get_mut_segment(&key);
-		segment.put(Item::new(key, value));
+		self.clock.on_request();
+		let now = self.clock.now();
+		let mut item = build(Item::new_at(key, value, now));
+		if item.expires_at.is_none() {
+			item.expires_at = self.default_ttl.map(|ttl| now + ttl);
+		}
+		self.expiring |= item.expires_at.is_some();
+		self.record_access(&key);
+		if self.expiration_sweep_buckets > 0 {
+			self.sweep_expired(self.expiration_sweep_buckets);
+		}
+		if self.expiring {
+			self.get_mut_segment(&key).remove_if_expired(&key, now);
+		}
+		let previous_value = self.peek(&key).map(|item| item.value);
+		if previous_value.is_some() {
+			// An item that outgrew its bucket comes back to be put again
+			match self.get_mut_segment(&key).update(item) {
+				Ok(previous_value) => return (Some(previous_value), Vec::new()),
+				Err(outgrown_item) => item = outgrown_item,
+			}
+		} else if let Some(admission_window) = &mut self.admission_window {
+			// Keys of the window are never in the buckets, an existing key is replaced in the window
+			let size = item.get_size();
+			let previous_item = admission_window.put(item);
+			if let Some(previous_item) = previous_item.filter(|previous_item| !previous_item.is_expired(now)) {
+				return (Some(previous_item.value), Vec::new());
+			}
+			self.hit_ratio.record_miss_bytes(size);
+			// Only the item the window pushes out has to be admitted, an expired one is reclaimed
+			let overflow = admission_window.pop_overflow();
+			let Some(candidate) = overflow.filter(|candidate| !candidate.is_expired(now)) else {
+				return (None, Vec::new());
+			};
+			if !self.admit(&candidate.key) {
+				debug!("Dash: admission filter rejected key {:?}", candidate.key);
+				return (None, vec![(candidate.key, candidate.value)]);
+			}
+			item = candidate;
+		} else {
+			// A new key is put after a miss, which tells how many bytes were missed
+			self.hit_ratio.record_miss_bytes(item.get_size());
+			if !self.admit(&key) {
+				debug!("Dash: admission filter rejected key {:?}", key);
+				return (None, vec![(key, value)]);
+			}
+		}
+
+		let key = item.key;
+		if self.expiring {
+			// Expired items make room before any live item is evicted or the segment splits
+			self.get_mut_segment(&key).remove_expired_items_for(&key, now);
+		}
+		let evicted_items = match self.mode {
+			DashMode::Cache if self.eviction_pool.is_some() => self.put_sampled(item),
+			DashMode::Cache => self.get_mut_segment(&key).put(item),
+			DashMode::Table => loop {
+				let segment_index = self.get_segment_index(&key);
+				match self.segments[segment_index].try_put(item) {
+					Ok(()) => break Vec::new(),
+					Err(rejected) => item = rejected,
+				}
+				if !self.split_segment(segment_index) {
+					break self.segments[segment_index].put(item);
+				}
+			},
+		};
+		(previous_value, into_pairs(evicted_items))
 	}
 
 	/// Returns the value of key if exists wrapper in Some ans None otherwise
@@ -50,21 +303,217 @@ where
 	/// As a side effect makes updates according to the eviction policy.
 	pub fn get_and_update_item(&mut self, key: &K) -> Option<&V> {
 		debug!("Dash: {:?}", self);
-		let segment = self.get_mut_segment(key);
-		let data = segment.get(key)?;
+		self.clock.on_request();
+		self.record_access(key);
+		let window_item = self
+			.admission_window
+			.as_ref()
+			.and_then(|admission_window| admission_window.peek(key));
+		if let Some(expired) = window_item.map(|item| item.is_expired(self.clock.now())) {
+			let admission_window = self.admission_window.as_mut().unwrap();
+			if expired {
+				admission_window.remove(key);
+				self.hit_ratio.record_miss();
+				return None;
+			}
+			let item = admission_window.get(key).unwrap();
+			self.hit_ratio.record_hit(item.get_size());
+			return Some(&item.value);
+		}
+		let segment_index = self.get_segment_index(key);
+		if self.expiring && self.segments[segment_index].remove_if_expired(key, self.clock.now()) {
+			self.hit_ratio.record_miss();
+			return None;
+		}
+		let Some(data) = self.segments[segment_index].get(key) else {
+			self.hit_ratio.record_miss();
+			return None;
+		};
+		self.hit_ratio.record_hit(data.get_size());
 		Some(&data.value)
 	}
 
-	fn get_mut_segment(&mut self, key: &K) -> &mut DashSegment<K, V> {
-		let segment_index = get_index(key, self.segments.len());
+	/// Returns the object and byte hit ratios, counting a hit or a miss for every `get_and_update_item`.
+	/// The bytes of a miss are counted when `put` gets the new key, which is how a simulator drives the cache.
+	pub fn hit_ratio(&self) -> &HitRatio {
+		&self.hit_ratio
+	}
+
+	/// Removes the key from Dash and returns its value if it was present and not expired.
+	pub fn remove(&mut self, key: &K) -> Option<V> {
+		debug!("Dash: {:?}", self);
+		let window_item = self
+			.admission_window
+			.as_mut()
+			.and_then(|admission_window| admission_window.remove(key));
+		let item = match window_item {
+			Some(item) => item,
+			None => self.get_mut_segment(key).remove(key)?,
+		};
+		(!item.is_expired(self.clock.now())).then_some(item.value)
+	}
+
+	/// Removes the expired items of the next `num_of_buckets` buckets, walking the segments incrementally.
+	///
+	/// Called on every put with `DashSettings::expiration_sweep_buckets`, so expired items that are never
+	/// looked up again free their slots without waiting for their bucket to fill up.
+	/// Returns how many items were removed.
+	pub fn sweep_expired(&mut self, num_of_buckets: usize) -> usize {
+		let now = self.clock.now();
+		let mut removed = 0;
+		for _ in 0..num_of_buckets {
+			let (segment_index, bucket_index) = self.sweep_cursor;
+			let segment_index = segment_index % self.segments.len();
+			let segment = &mut self.segments[segment_index];
+			removed += segment.remove_expired_from_bucket(bucket_index, now);
+			self.sweep_cursor = if bucket_index + 1 < segment.segment_size + segment.stash_size {
+				(segment_index, bucket_index + 1)
+			} else {
+				(segment_index + 1, 0)
+			};
+		}
+		removed
+	}
+
+	/// Returns how evenly the items are spread over the normal buckets of all the segments.
+	pub fn bucket_load_skew(&self) -> BucketLoadSkew {
+		let loads: Vec<usize> = self
+			.segments
+			.iter()
+			.flat_map(|segment| segment.buckets.iter().map(|bucket| bucket.size()))
+			.collect();
+		let skew = BucketLoadSkew::from_loads(&loads);
+		info!("Dash: {}", skew);
+		skew
+	}
+
+	/// Returns the number of bytes the ghost histories of all the segments take.
+	pub fn ghost_memory_usage(&self) -> usize {
+		let usage = self.segments.iter().map(|segment| segment.ghost_memory_usage()).sum();
+		info!("Dash: ghost histories take {} bytes", usage);
+		usage
+	}
+
+	fn record_access(&mut self, key: &K) {
+		if let Some(admission_filter) = &mut self.admission_filter {
+			admission_filter.record(hash(key, &self.hash_builder));
+		}
+	}
+
+	/// Returns whether the admission filter lets `key` replace the item its segment would evict.
+	///
+	/// A table only evicts once it cannot grow any more, so it admits every key.
+	fn admit(&self, key: &K) -> bool {
+		let Some(admission_filter) = &self.admission_filter else {
+			return true;
+		};
+		if self.mode == DashMode::Table {
+			return true;
+		}
+		match self.segments[self.get_segment_index(key)].peek_victim(key) {
+			Some(victim) => admission_filter.admit(hash(key, &self.hash_builder), hash(&victim.key, &self.hash_builder)),
+			None => true,
+		}
+	}
+
+	/// Puts the item into its segment, evicting the victim the shared eviction pool picks if there is no room.
+	///
+	/// Only a victim of the segment whose eviction frees a slot for the key is picked,
+	/// otherwise the segment evicts from the candidate buckets of the key.
+	fn put_sampled(&mut self, item: Item<K, V>) -> Vec<Item<K, V>> {
+		let segment_index = self.get_segment_index(&item.key);
+		let Err(item) = self.segments[segment_index].try_put(item) else {
+			return Vec::new();
+		};
+
+		let mut eviction_pool = self
+			.eviction_pool
+			.take()
+			.expect("sampled eviction needs an eviction pool");
+		let now = self.clock.now();
+		let victim_key = eviction_pool.take_victim(
+			// A victim of another segment would not free room for the key, only its segment is sampled.
+			// The candidates of other segments sampled by earlier rounds stay in the pool for their own rounds
+			|rng| self.segments[segment_index].sample_key(rng),
+			|victim_key| self.peek(victim_key).is_some(),
+			|a, b| {
+				let eviction_policy = self.segments[0].get_eviction_policy();
+				eviction_policy.compare_victims(self.peek(a).unwrap(), self.peek(b).unwrap(), now)
+			},
+			|victim_key| {
+				self.get_segment_index(victim_key) == segment_index
+					&& self.segments[segment_index].can_evict_for(victim_key, &item.key)
+			},
+		);
+		self.eviction_pool = Some(eviction_pool);
+
+		// The segment takes it from there, evicting from the candidate buckets if the victim did not free enough room
+		let segment = &mut self.segments[segment_index];
+		let mut evicted_items: Vec<_> = victim_key
+			.and_then(|victim_key| segment.evict_for(&victim_key, &item.key))
+			.into_iter()
+			.collect();
+		evicted_items.extend(segment.put(item));
+		evicted_items
+	}
+
+	/// Returns a reference to the item with `key` without updating it.
+	fn peek(&self, key: &K) -> Option<&Item<K, V>> {
+		self.segments[self.get_segment_index(key)].peek(key)
+	}
+
+	fn get_mut_segment(&mut self, key: &K) -> &mut DashSegment<K, V, S> {
+		let segment_index = self.get_segment_index(key);
 		&mut self.segments[segment_index]
 	}
+
+	fn get_segment_index(&self, key: &K) -> usize {
+		match self.mode {
+			DashMode::Cache => self.layout.segment_index(hash(key, &self.hash_builder)),
+			DashMode::Table => {
+				let directory_index = HashLayout::directory_index(hash(key, &self.hash_builder), self.global_depth);
+				self.directory[directory_index]
+			}
+		}
+	}
+
+	/// Splits the segment at `segment_index`, doubling the directory first if its local depth
+	/// equals the global depth.
+	///
+	/// Returns false if the segment cannot be split any further.
+	fn split_segment(&mut self, segment_index: usize) -> bool {
+		let local_depth = self.segments[segment_index].local_depth;
+		if local_depth >= MAX_GLOBAL_DEPTH {
+			return false;
+		}
+		if local_depth == self.global_depth {
+			self.directory = (0..self.directory.len() * 2).map(|i| self.directory[i >> 1]).collect();
+			self.global_depth += 1;
+			info!("Dash: directory doubled to global depth {}", self.global_depth);
+		}
+
+		let sibling = self.segments[segment_index].split();
+		let sibling_index = self.segments.len();
+		self.segments.push(sibling);
+
+		// The entries pointing at the split segment form a contiguous range,
+		// the ones with the new bit set now belong to the sibling
+		let split_bit = self.global_depth - 1 - local_depth;
+		for (directory_index, entry) in self.directory.iter_mut().enumerate() {
+			if *entry == segment_index && (directory_index >> split_bit) & 1 == 1 {
+				*entry = sibling_index;
+			}
+		}
+		debug!("Dash: split segment {} into {}", segment_index, sibling_index);
+		true
+	}
 }
 
-impl<K, V> Display for Dash<K, V>
+impl<K, V, S> Display for Dash<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug + Display,
 	V: Eq + Copy + Debug + Display,
+	S: BuildHasher + Clone + Debug,
 {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		for segment in &self.segments {
@@ -73,3 +522,553 @@ where
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::clock::VirtualClock;
+	use crate::eviction_policy::EvictionPolicy;
+	use crate::hasher::{FastBuildHasher, HasherKind};
+	use dash_settings::InsertMode;
+	use std::sync::Arc;
+
+	fn table_settings() -> DashSettings {
+		DashSettings {
+			num_of_segments: 1,
+			num_of_normal_buckets: 4,
+			num_of_stash_buckets: 2,
+			bucket_size: 2,
+			mode: DashMode::Table,
+			..DashSettings::default()
+		}
+	}
+
+	#[test]
+	fn test_table_mode_grows_instead_of_evicting() {
+		let mut dash: Dash<i64, i64> = Dash::new(table_settings()).unwrap();
+		for key in 0..1000 {
+			dash.put(key, key * 2);
+		}
+
+		assert!(dash.segments.len() > 1);
+		assert_eq!(dash.directory.len(), 1 << dash.global_depth);
+		for key in 0..1000 {
+			assert_eq!(dash.get_and_update_item(&key), Some(&(key * 2)));
+		}
+	}
+
+	#[test]
+	fn test_table_mode_local_depth_never_exceeds_global_depth() {
+		let mut dash: Dash<i64, i64> = Dash::new(table_settings()).unwrap();
+		for key in 0..500 {
+			dash.put(key, key);
+		}
+
+		for segment in &dash.segments {
+			assert!(segment.local_depth <= dash.global_depth);
+			let pointers = dash
+				.directory
+				.iter()
+				.filter(|&&entry| std::ptr::eq(&dash.segments[entry], segment));
+			assert_eq!(pointers.count(), 1 << (dash.global_depth - segment.local_depth));
+		}
+	}
+
+	#[test]
+	fn test_cache_mode_keeps_fixed_segments() {
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			num_of_segments: 3,
+			mode: DashMode::Cache,
+			..table_settings()
+		})
+		.unwrap();
+		for key in 0..1000 {
+			dash.put(key, key);
+		}
+		assert_eq!(dash.segments.len(), 3);
+	}
+
+	#[test]
+	fn test_put_returns_evicted_itemss() {
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			mode: DashMode::Cache,
+			..table_settings()
+		})
+		.unwrap();
+		let mut evicted = Vec::new();
+		for key in 0..100 {
+			evicted.extend(dash.put(key, key).1);
+		}
+
+		// Every key is either still cached or was reported as evicted
+		let cached: Vec<i64> = (0..100).filter(|key| dash.get_and_update_item(key).is_some()).collect();
+		assert_eq!(cached.len() + evicted.len(), 100);
+		assert!(evicted.iter().all(|(key, value)| key == value && !cached.contains(key)));
+	}
+
+	#[test]
+	fn test_remove() {
+		for mode in [DashMode::Cache, DashMode::Table] {
+			for insert_mode in [InsertMode::StashFirst, InsertMode::Balanced] {
+				let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+					num_of_segments: 2,
+					mode,
+					insert_mode,
+					..table_settings()
+				})
+				.unwrap();
+				let mut cached = Vec::new();
+				for key in 0..40 {
+					if dash.put(key, key).1.is_empty() {
+						cached.push(key);
+					}
+					cached.retain(|cached_key| dash.get_and_update_item(cached_key).is_some());
+				}
+
+				for key in cached {
+					assert_eq!(dash.remove(&key), Some(key));
+					assert_eq!(dash.get_and_update_item(&key), None);
+					assert_eq!(dash.remove(&key), None);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_put_replaces_existing_value() {
+		for mode in [DashMode::Cache, DashMode::Table] {
+			for insert_mode in [InsertMode::StashFirst, InsertMode::Balanced] {
+				let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+					mode,
+					insert_mode,
+					..table_settings()
+				})
+				.unwrap();
+				assert_eq!(dash.put(1, 10), (None, vec![]));
+				assert_eq!(dash.put(1, 11), (Some(10), vec![]));
+				// A hit may promote the item out of the stash, the key must not be duplicated on the next put
+				assert_eq!(dash.get_and_update_item(&1), Some(&11));
+				assert_eq!(dash.put(1, 12), (Some(11), vec![]));
+
+				assert_eq!(dash.remove(&1), Some(12));
+				assert_eq!(dash.get_and_update_item(&1), None);
+			}
+		}
+	}
+
+	#[test]
+	fn test_segment_and_bucket_choices_are_decorrelated() {
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			num_of_segments: 2,
+			..DashSettings::default()
+		})
+		.unwrap();
+		for key in 0..500 {
+			dash.put(key, key);
+		}
+
+		let skew = dash.bucket_load_skew();
+		assert_eq!(skew.num_of_buckets, 56);
+		assert_eq!(skew.empty_buckets, 0);
+		assert!(skew.coefficient_of_variation < 0.5);
+	}
+
+	#[test]
+	fn test_with_hasher() {
+		// The identity hasher sends small keys to the same bucket, so it is left out
+		for hash_builder in [HasherKind::Default, HasherKind::Fast, HasherKind::Seeded(42)] {
+			let mut dash: Dash<i64, i64, HasherKind> = Dash::with_hasher(table_settings(), hash_builder).unwrap();
+			for key in 0..200 {
+				dash.put(key, key * 2);
+			}
+			for key in 0..200 {
+				assert_eq!(dash.get_and_update_item(&key), Some(&(key * 2)));
+			}
+		}
+
+		let mut dash = Dash::with_hasher(
+			DashSettings {
+				num_of_segments: 2,
+				..DashSettings::default()
+			},
+			FastBuildHasher,
+		)
+		.unwrap();
+		for key in 0..500_i64 {
+			dash.put(key, key);
+		}
+		assert_eq!(dash.bucket_load_skew().empty_buckets, 0);
+	}
+
+	#[test]
+	fn test_unsupported_settings_are_rejected() {
+		let arc_with_bytes = Dash::<i64, i64>::new(DashSettings {
+			eviction_policy: EvictionPolicy::Arc,
+			bucket_capacity_bytes: Some(64),
+			..DashSettings::default()
+		});
+		assert_eq!(arc_with_bytes.err(), Some(UnsupportedSettings::BucketCapacityBytes(64)));
+
+		let sampled_arc = Dash::<i64, i64>::new(DashSettings {
+			eviction_policy: EvictionPolicy::Arc,
+			eviction_scope: EvictionScope::SampledSegment,
+			..DashSettings::default()
+		});
+		assert_eq!(
+			sampled_arc.err(),
+			Some(UnsupportedSettings::EvictionScope(EvictionScope::SampledSegment))
+		);
+	}
+
+	#[test]
+	fn test_tiny_lfu_rejects_one_hit_wonders() {
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			admission_policy: AdmissionPolicy::TinyLfu,
+			..DashSettings::default()
+		})
+		.unwrap();
+		for key in 0..4 {
+			assert_eq!(dash.put(key, key), (None, vec![]));
+			dash.get_and_update_item(&key);
+		}
+
+		// A new key is rejected while it was accessed less often than the victim
+		assert_eq!(dash.put(100, 100), (None, vec![(100, 100)]));
+		assert_eq!(dash.get_and_update_item(&100), None);
+
+		for _ in 0..3 {
+			dash.get_and_update_item(&100);
+		}
+		let (_, evicted) = dash.put(100, 100);
+		assert!(matches!(evicted[..], [(key, _)] if key < 4));
+		assert_eq!(dash.get_and_update_item(&100), Some(&100));
+	}
+
+	#[test]
+	fn test_w_tiny_lfu_admits_the_items_the_window_pushes_out() {
+		// 4 items in the buckets and 1 in the window
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			admission_policy: AdmissionPolicy::WTinyLfu,
+			..DashSettings::default()
+		})
+		.unwrap();
+		for key in 0..4 {
+			assert_eq!(dash.put(key, key), (None, vec![]));
+			for _ in 0..4 {
+				dash.get_and_update_item(&key);
+			}
+		}
+
+		// A new key waits in the window, where it is found, and replaces the item there
+		assert_eq!(dash.put(100, 100), (None, vec![]));
+		assert_eq!(dash.get_and_update_item(&100), Some(&100));
+		assert_eq!(dash.put(100, 101), (Some(100), vec![]));
+
+		// The window pushes it out for the next new key and it is rejected, the popular items stay
+		assert_eq!(dash.put(200, 200), (None, vec![(100, 101)]));
+		assert_eq!(dash.get_and_update_item(&100), None);
+		assert!((0..4).all(|key| dash.get_and_update_item(&key).is_some()));
+		assert_eq!(dash.remove(&200), Some(200));
+		assert_eq!(dash.get_and_update_item(&200), None);
+	}
+
+	#[test]
+	fn test_every_eviction_policy_keeps_items_consistent() {
+		let eviction_policies = (0..).map_while(EvictionPolicy::from_usize);
+		for eviction_policy in eviction_policies {
+			let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+				num_of_normal_buckets: 4,
+				num_of_stash_buckets: 2,
+				bucket_size: 4,
+				eviction_policy: eviction_policy.clone(),
+				..DashSettings::default()
+			})
+			.unwrap();
+			let mut evicted = 0;
+			for round in 0..2000_i64 {
+				let key = (round * 7919) % 97;
+				if dash.get_and_update_item(&key).is_none() {
+					evicted += dash.put(key, key * 2).1.len();
+				}
+			}
+
+			let cached = (0..97).filter(|key| dash.get_and_update_item(key).is_some()).count();
+			assert!(cached <= 6 * 4, "{:?} holds {} items", eviction_policy, cached);
+			assert!(evicted > 0, "{:?} never evicted", eviction_policy);
+			assert!(
+				(0..97).all(|key| dash.get_and_update_item(&key).is_none_or(|&value| value == key * 2)),
+				"{:?}",
+				eviction_policy
+			);
+		}
+	}
+
+	#[test]
+	fn test_aging_lfu_variants_follow_shifting_popularity() {
+		let phase_hits = |eviction_policy: EvictionPolicy| {
+			let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+				num_of_normal_buckets: 4,
+				num_of_stash_buckets: 2,
+				bucket_size: 4,
+				eviction_policy,
+				..DashSettings::default()
+			})
+			.unwrap();
+			let mut hits = 0;
+			// The popular keys move from 0..16 to 100..116, only the hits of the second phase count
+			for (phase, first_key) in [0_i64, 100].into_iter().enumerate() {
+				for round in 0..3000_i64 {
+					let key = first_key + (round * 7) % 16;
+					if dash.get_and_update_item(&key).is_some() {
+						hits += phase;
+					} else {
+						dash.put(key, key);
+					}
+				}
+			}
+			hits
+		};
+
+		let lfu_hits = phase_hits(EvictionPolicy::Lfu);
+		assert!(phase_hits(EvictionPolicy::LfuAging) > lfu_hits);
+		assert!(phase_hits(EvictionPolicy::Lfuda) > lfu_hits);
+	}
+
+	#[test]
+	fn test_lfuda_ages_with_every_eviction_scope() {
+		for eviction_scope in [
+			EvictionScope::Segment,
+			EvictionScope::SampledSegment,
+			EvictionScope::SampledSharedPool,
+		] {
+			let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+				num_of_segments: 1,
+				num_of_normal_buckets: 4,
+				num_of_stash_buckets: 2,
+				bucket_size: 4,
+				eviction_policy: EvictionPolicy::Lfuda,
+				eviction_scope,
+				..DashSettings::default()
+			})
+			.unwrap();
+			for key in 0..100 {
+				dash.put(key, key);
+			}
+
+			// Every evicted item sets the age of the bucket it was evicted from to its priority, at least 1
+			let segment = &dash.segments[0];
+			let max_age = segment
+				.buckets
+				.iter()
+				.chain(&segment.stash_buckets)
+				.map(|bucket| bucket.get_policy_state().get_age())
+				.fold(0.0, f64::max);
+			assert!(max_age >= 1.0, "{:?}", eviction_scope);
+		}
+	}
+
+	#[test]
+	fn test_sampled_eviction_is_reproducible() {
+		for eviction_scope in [EvictionScope::SampledSegment, EvictionScope::SampledSharedPool] {
+			let evictions = |sampling_seed: u64| {
+				let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+					num_of_segments: 2,
+					num_of_normal_buckets: 4,
+					num_of_stash_buckets: 2,
+					bucket_size: 4,
+					eviction_policy: EvictionPolicy::TimestampLRU,
+					eviction_scope,
+					sampling_seed,
+					..DashSettings::default()
+				})
+				.unwrap();
+				let mut evicted_keys = Vec::new();
+				for round in 0..2000_i64 {
+					let key = (round * 7919) % 197;
+					if dash.get_and_update_item(&key).is_none() {
+						evicted_keys.extend(dash.put(key, key * 2).1.into_iter().map(|(key, _)| key));
+					}
+				}
+				assert!((0..197).all(|key| dash.get_and_update_item(&key).is_none_or(|&value| value == key * 2)));
+				evicted_keys
+			};
+
+			let evicted_keys = evictions(1);
+			assert!(!evicted_keys.is_empty(), "{:?}", eviction_scope);
+			assert_eq!(evicted_keys, evictions(1), "{:?}", eviction_scope);
+		}
+	}
+
+	#[test]
+	fn test_expired_items_are_reclaimed_before_live_ones() {
+		let settings = DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			..DashSettings::default()
+		};
+		let mut dash: Dash<i64, i64> = Dash::new(settings.clone()).unwrap();
+		dash.put_with_ttl(0, 0, Duration::ZERO);
+		dash.put_with_ttl(1, 1, Duration::ZERO);
+		dash.put_with_ttl(2, 2, Duration::from_secs(3600));
+		dash.put(3, 3);
+		assert_eq!(dash.get_and_update_item(&0), None);
+
+		// The segment is full, but 1 expired so nothing live is evicted
+		assert_eq!(dash.put(4, 4), (None, vec![]));
+		for key in 2..5 {
+			assert_eq!(dash.get_and_update_item(&key), Some(&key));
+		}
+		// A new TTL replaces the previous one
+		assert_eq!(dash.put_with_ttl(2, 5, Duration::ZERO), (Some(2), vec![]));
+		assert_eq!(dash.get_and_update_item(&2), None);
+
+		// The sweeper reclaims expired items that are never looked up again
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			default_ttl: Some(Duration::ZERO),
+			..settings
+		})
+		.unwrap();
+		dash.put(0, 0);
+		// Putting 1 reclaimed 0 from its candidate buckets
+		dash.put(1, 1);
+		assert_eq!(dash.sweep_expired(2), 1);
+		assert_eq!(
+			dash.segments[0].buckets[0].size() + dash.segments[0].stash_buckets[0].size(),
+			0
+		);
+	}
+
+	#[test]
+	fn test_put_with_size_resizes_existing_keys() {
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+			num_of_segments: 1,
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 4,
+			bucket_capacity_bytes: Some(4),
+			eviction_policy: EvictionPolicy::Gdsf,
+			..DashSettings::default()
+		})
+		.unwrap();
+		for key in 0..8 {
+			dash.put_with_size(key, key, 1);
+		}
+		// 0 grows to 2 bytes and is put again, the victim is the item with the lowest priority
+		assert_eq!(dash.put_with_size(0, 10, 2), (Some(0), vec![(1, 1)]));
+		let bucket = &dash.segments[0].buckets[0];
+		// The age is the priority of 1, 0 was accessed twice and takes 2 bytes
+		assert_eq!(
+			dash.peek(&0).map(|item| item.policy_state.get_priority()),
+			Some(bucket.get_policy_state().get_age() + 2.0 / 2.0)
+		);
+
+		assert_eq!(dash.put_with_size(0, 11, 4), (Some(10), vec![(2, 2), (3, 3)]));
+		assert_eq!(dash.segments[0].buckets[0].used_bytes(), 4);
+
+		// Shrinking never evicts and GDSF recomputes the priority with the new size
+		assert_eq!(dash.put_with_size(0, 12, 1), (Some(11), vec![]));
+		let bucket = &dash.segments[0].buckets[0];
+		assert_eq!(
+			dash.peek(&0).map(|item| item.policy_state.get_priority()),
+			Some(bucket.get_policy_state().get_age() + 4.0)
+		);
+		assert_eq!(dash.get_and_update_item(&0), Some(&12));
+	}
+
+	#[test]
+	fn test_hit_ratio_counts_lookups() {
+		let mut dash: Dash<i64, i64> = Dash::new(DashSettings::default()).unwrap();
+		assert_eq!(dash.get_and_update_item(&1), None);
+		dash.put_with_size(1, 1, 3);
+		assert_eq!(dash.get_and_update_item(&1), Some(&1));
+		assert_eq!(dash.hit_ratio().object_hit_ratio(), 0.5);
+		assert_eq!(dash.hit_ratio().byte_hit_ratio(), 0.5);
+
+		// A lookup that is not followed by a put is still a miss
+		assert_eq!(dash.get_and_update_item(&2), None);
+		assert_eq!(dash.hit_ratio().misses, 2);
+		assert_eq!(dash.hit_ratio().byte_hit_ratio(), 0.5);
+	}
+
+	#[test]
+	fn test_large_put_returns_every_evicted_item() {
+		for eviction_scope in [EvictionScope::Bucket, EvictionScope::Segment] {
+			let mut dash: Dash<i64, i64> = Dash::new(DashSettings {
+				num_of_segments: 1,
+				num_of_normal_buckets: 1,
+				num_of_stash_buckets: 1,
+				bucket_size: 4,
+				bucket_capacity_bytes: Some(4),
+				eviction_policy: EvictionPolicy::Fifo,
+				eviction_scope,
+				..DashSettings::default()
+			})
+			.unwrap();
+			for key in 0..8 {
+				assert_eq!(dash.put_with_size(key, key, 1), (None, vec![]));
+			}
+
+			// The new item takes a whole bucket, the four oldest items of the bucket it lands in make room for it
+			let (_, evicted) = dash.put_with_size(100, 100, 4);
+			assert_eq!(evicted, vec![(0, 0), (1, 1), (2, 2), (3, 3)], "{:?}", eviction_scope);
+			assert_eq!(dash.get_and_update_item(&100), Some(&100));
+			assert!((4..8).all(|key| dash.get_and_update_item(&key) == Some(&key)));
+		}
+	}
+
+	#[test]
+	fn test_virtual_clock_makes_runs_reproducible() {
+		let evictions = || {
+			let clock = Arc::new(VirtualClock::logical(Duration::from_millis(1)));
+			let mut dash: Dash<i64, i64> = Dash::with_clock(
+				DashSettings {
+					num_of_normal_buckets: 4,
+					num_of_stash_buckets: 2,
+					bucket_size: 4,
+					eviction_policy: EvictionPolicy::Hyperbolic,
+					eviction_scope: EvictionScope::Segment,
+					..DashSettings::default()
+				},
+				DefaultBuildHasher::default(),
+				clock,
+			)
+			.unwrap();
+			let mut evicted_keys = Vec::new();
+			for round in 0..2000_i64 {
+				let key = (round * round) % 101;
+				if dash.get_and_update_item(&key).is_none() {
+					evicted_keys.extend(dash.put(key, key).1.into_iter().map(|(key, _)| key));
+				}
+			}
+			evicted_keys
+		};
+		let evicted_keys = evictions();
+		assert!(!evicted_keys.is_empty());
+		assert_eq!(evicted_keys, evictions());
+
+		// Items expire when the trace says so, however long the run takes
+		let clock = Arc::new(VirtualClock::new());
+		let mut dash: Dash<i64, i64> =
+			Dash::with_clock(DashSettings::default(), DefaultBuildHasher::default(), clock.clone()).unwrap();
+		dash.put_with_ttl(0, 0, Duration::from_secs(10));
+		clock.advance_to(Duration::from_secs(9));
+		assert_eq!(dash.get_and_update_item(&0), Some(&0));
+		clock.advance_to(Duration::from_secs(10));
+		assert_eq!(dash.get_and_update_item(&0), None);
+
+		// The cache advances the clock it was given
+		let mut dash: Dash<i64, i64> =
+			Dash::with_clock(DashSettings::default(), DefaultBuildHasher::default(), clock.clone()).unwrap();
+		dash.put_with_ttl(0, 0, Duration::from_secs(10));
+		dash.advance_clock(Duration::from_secs(20));
+		assert_eq!(clock.elapsed(), Duration::from_secs(20));
+		assert_eq!(dash.get_and_update_item(&0), None);
+	}
+}
@@ -0,0 +1,129 @@
+//! Adaptive Replacement Cache (ARC) bookkeeping of a `DashSegment`.
+//!
+//! The resident lists live in the buckets: an item is in T1 until its first hit (`lfu_counter == 0`)
+//! and in T2 afterwards, ordered by `timestamp`. This struct keeps what does not fit in the buckets:
+//! the ghost lists B1 and B2 and the adaptive target size `p` of T1.
+
+use crate::shared::ghost_history::GhostHistory;
+
+/// Which ghost list, if any, remembered a key that is put again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArcGhostHit {
+	None,
+	/** B1, the key was evicted from T1 */
+	Recency,
+	/** B2, the key was evicted from T2 */
+	Frequency,
+}
+
+#[derive(Debug)]
+pub struct ArcState {
+	/// Number of items the segment can hold (`c` in the ARC paper).
+	capacity: usize,
+	/// Target size of T1 (`p` in the ARC paper).
+	target: usize,
+	/// B1, hashes of the keys evicted from T1.
+	recency_ghost: GhostHistory,
+	/// B2, hashes of the keys evicted from T2.
+	frequency_ghost: GhostHistory,
+}
+
+impl ArcState {
+	/// Creates the state of a segment holding `capacity` items, whose ghost lists hold up to `ghost_capacity` hashes each.
+	///
+	/// The ARC paper bounds each ghost list by `capacity`, a smaller bound trades hit ratio for memory.
+	pub fn new(capacity: usize, ghost_capacity: usize) -> Self {
+		ArcState {
+			capacity,
+			target: 0,
+			recency_ghost: GhostHistory::new(ghost_capacity),
+			frequency_ghost: GhostHistory::new(ghost_capacity),
+		}
+	}
+
+	pub fn get_ghost_capacity(&self) -> usize {
+		self.recency_ghost.capacity()
+	}
+
+	/// Returns the number of bytes taken by the ghost lists.
+	pub fn memory_usage(&self) -> usize {
+		self.recency_ghost.memory_usage() + self.frequency_ghost.memory_usage()
+	}
+
+	/// Looks a key that is not resident up in the ghost lists.
+	pub fn find_ghost(&self, hash: u64) -> ArcGhostHit {
+		if self.recency_ghost.contains(hash) {
+			ArcGhostHit::Recency
+		} else if self.frequency_ghost.contains(hash) {
+			ArcGhostHit::Frequency
+		} else {
+			ArcGhostHit::None
+		}
+	}
+
+	/// Handles a miss of the key with `hash`, given the current sizes of T1 and T2.
+	///
+	/// A ghost hit forgets the key and moves `p` towards the list it was evicted from,
+	/// otherwise the ghost lists are trimmed to make room for one more key.
+	/// Returns where the key was found.
+	pub fn on_miss(&mut self, hash: u64, recency_len: usize, frequency_len: usize) -> ArcGhostHit {
+		let ghost_hit = self.find_ghost(hash);
+		match ghost_hit {
+			ArcGhostHit::Recency => {
+				let delta = (self.frequency_ghost.len() / self.recency_ghost.len()).max(1);
+				self.target = (self.target + delta).min(self.capacity);
+				self.recency_ghost.forget(hash);
+			}
+			ArcGhostHit::Frequency => {
+				let delta = (self.recency_ghost.len() / self.frequency_ghost.len()).max(1);
+				self.target = self.target.saturating_sub(delta);
+				self.frequency_ghost.forget(hash);
+			}
+			ArcGhostHit::None => {
+				let ghost_len = self.recency_ghost.len() + self.frequency_ghost.len();
+				if recency_len + self.recency_ghost.len() >= self.capacity {
+					self.recency_ghost.forget_oldest();
+				} else if recency_len + frequency_len + ghost_len >= 2 * self.capacity {
+					self.frequency_ghost.forget_oldest();
+				}
+			}
+		}
+		ghost_hit
+	}
+
+	/// Returns whether the ARC `REPLACE` step takes its victim from T1 rather than T2.
+	pub fn replace_from_recency(&self, recency_len: usize, ghost_hit: ArcGhostHit) -> bool {
+		recency_len > 0
+			&& (recency_len > self.target || (ghost_hit == ArcGhostHit::Frequency && recency_len == self.target))
+	}
+
+	/// Remembers an evicted key in B1 if it was evicted from T1, or in B2 otherwise.
+	pub fn remember(&mut self, hash: u64, from_recency: bool) {
+		let ghost = if from_recency {
+			&mut self.recency_ghost
+		} else {
+			&mut self.frequency_ghost
+		};
+		ghost.remember(hash);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_ghost_hits_adapt_target() {
+		let mut arc = ArcState::new(4, 4);
+		arc.remember(1, true);
+		arc.remember(2, false);
+
+		assert_eq!(arc.on_miss(1, 2, 2), ArcGhostHit::Recency);
+		assert_eq!(arc.target, 1);
+		assert_eq!(arc.find_ghost(1), ArcGhostHit::None);
+
+		assert_eq!(arc.on_miss(2, 2, 2), ArcGhostHit::Frequency);
+		assert_eq!(arc.target, 0);
+		assert_eq!(arc.on_miss(3, 2, 2), ArcGhostHit::None);
+	}
+}
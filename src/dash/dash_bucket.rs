@@ -1,43 +1,174 @@
+use crate::clock::{real_clock, SharedClock};
 use crate::eviction_policy::EvictionPolicy;
+use crate::shared::ghost_history::GhostHistory;
 use crate::shared::item::Item;
+use crate::shared::policy_state::BucketPolicyState;
 use crate::shared::traits::bucket::Bucket;
+use crate::shared::utils::{get_fingerprint, get_ghost_capacity, hash};
 use std::{
 	fmt::{Debug, Display, Formatter},
-	hash::Hash,
+	hash::{BuildHasher, Hash},
 };
 
+/// Number of overflow fingerprints a normal bucket can record for its items that live in the stash.
+const OVERFLOW_SLOTS: usize = 4;
+
 #[derive(Debug)]
-pub struct DashBucket<K, V>
+pub struct DashBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
 	// TODO: consider using a linked list for O(1) changes
 	items: Vec<Item<K, V>>,
+	/// One byte per slot, `fingerprints[i]` is the fingerprint of `items[i].key`.
+	/// Kept as a contiguous byte array so it can be probed with SIMD.
+	fingerprints: Vec<u8>,
+	/// Fingerprints of stashed items whose target bucket is this bucket or the previous one.
+	overflow_fingerprints: Vec<u8>,
+	/// `overflow_stash_indexes[i]` is the stash bucket holding the item of `overflow_fingerprints[i]`.
+	overflow_stash_indexes: Vec<usize>,
+	/// `overflow_members[i]` is true when the item's target bucket is the previous bucket,
+	/// i.e. this bucket is its probing bucket.
+	overflow_members: Vec<bool>,
+	/// Number of stashed items targeting this bucket that did not get an overflow fingerprint.
+	/// A lookup has to scan the stash while this is not zero.
+	overflow_count: usize,
 	max_size: usize,
+	/// Capacity in bytes, `None` if only the number of items is limited.
+	max_bytes: Option<usize>,
+	max_protected_size: usize,
 	// TODO: make this a reference with a lifetime
 	eviction_policy: EvictionPolicy,
+	policy_state: BucketPolicyState<K>,
+	clock: SharedClock,
+	hash_builder: S,
 }
 
-impl<K, V> DashBucket<K, V>
+impl<K, V, S> DashBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
-	pub fn new(max_size: usize, eviction_policy: EvictionPolicy) -> Self {
+	pub fn new(max_size: usize, max_protected_size: usize, eviction_policy: EvictionPolicy, hash_builder: S) -> Self {
 		DashBucket {
 			// TODO: consider creating a vector with a fixed size for better performance after initialization
 			items: Vec::new(),
+			fingerprints: Vec::with_capacity(max_size),
+			overflow_fingerprints: Vec::with_capacity(OVERFLOW_SLOTS),
+			overflow_stash_indexes: Vec::with_capacity(OVERFLOW_SLOTS),
+			overflow_members: Vec::with_capacity(OVERFLOW_SLOTS),
+			overflow_count: 0,
 			max_size,
+			max_bytes: None,
+			max_protected_size,
+			policy_state: BucketPolicyState::new(&eviction_policy, get_ghost_capacity(max_size, None)),
 			eviction_policy,
+			clock: real_clock(),
+			hash_builder,
+		}
+	}
+
+	/// Limits the total size of the items in the bucket to `max_bytes`, on top of the number of items.
+	pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+		self.max_bytes = max_bytes;
+		self
+	}
+
+	/// Takes the timestamps of the items from `clock` rather than from the wall clock.
+	pub fn with_clock(mut self, clock: SharedClock) -> Self {
+		self.clock = clock;
+		self
+	}
+
+	/// Sets how many evicted keys the bucket remembers, `None` for half its size.
+	pub fn with_ghost_capacity(mut self, ghost_capacity: Option<usize>) -> Self {
+		if let Some(ghost) = self.policy_state.get_ghost_mut() {
+			*ghost = GhostHistory::new(get_ghost_capacity(self.max_size, ghost_capacity));
+		}
+		self
+	}
+
+	/// Removes all the items from the bucket and returns them in their current order.
+	pub fn take_items(&mut self) -> Vec<Item<K, V>> {
+		self.fingerprints.clear();
+		self.policy_state.clear_positions();
+		std::mem::take(&mut self.items)
+	}
+
+	/// Returns the fingerprints of the items in the bucket.
+	pub fn get_fingerprints(&self) -> &[u8] {
+		&self.fingerprints
+	}
+
+	/// Records that an item with `fingerprint` was stored in stash bucket `stash_index`.
+	///
+	/// Returns false if all the overflow slots are taken.
+	pub fn add_overflow(&mut self, fingerprint: u8, stash_index: usize, is_member: bool) -> bool {
+		if self.overflow_fingerprints.len() == OVERFLOW_SLOTS {
+			return false;
+		}
+		self.overflow_fingerprints.push(fingerprint);
+		self.overflow_stash_indexes.push(stash_index);
+		self.overflow_members.push(is_member);
+		true
+	}
+
+	/// Removes an overflow record previously added with `add_overflow`.
+	///
+	/// Returns false if there is no such record.
+	pub fn remove_overflow(&mut self, fingerprint: u8, stash_index: usize, is_member: bool) -> bool {
+		let position = (0..self.overflow_fingerprints.len()).find(|&i| {
+			self.overflow_fingerprints[i] == fingerprint
+				&& self.overflow_stash_indexes[i] == stash_index
+				&& self.overflow_members[i] == is_member
+		});
+		match position {
+			Some(position) => {
+				self.overflow_fingerprints.swap_remove(position);
+				self.overflow_stash_indexes.swap_remove(position);
+				self.overflow_members.swap_remove(position);
+				true
+			}
+			None => false,
 		}
 	}
+
+	/// Returns the stash buckets that may hold an item with `fingerprint`.
+	pub fn get_overflow_stash_indexes(&self, fingerprint: u8, is_member: bool) -> impl Iterator<Item = usize> + '_ {
+		(0..self.overflow_fingerprints.len())
+			.filter(move |&i| self.overflow_fingerprints[i] == fingerprint && self.overflow_members[i] == is_member)
+			.map(|i| self.overflow_stash_indexes[i])
+	}
+
+	pub fn get_overflow_count(&self) -> usize {
+		self.overflow_count
+	}
+
+	pub fn increment_overflow_count(&mut self) {
+		self.overflow_count += 1;
+	}
+
+	pub fn decrement_overflow_count(&mut self) {
+		self.overflow_count -= 1;
+	}
+
+	/// Forgets all the overflow records of the bucket.
+	pub fn clear_overflow(&mut self) {
+		self.overflow_fingerprints.clear();
+		self.overflow_stash_indexes.clear();
+		self.overflow_members.clear();
+		self.overflow_count = 0;
+	}
 }
 
-impl<K, V> Bucket<K, V> for DashBucket<K, V>
+impl<K, V, S> Bucket<K, V> for DashBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
 	fn get_items(&self) -> &Vec<Item<K, V>> {
 		&self.items
@@ -51,15 +182,70 @@ where
 		self.max_size
 	}
 
+	fn get_max_bytes(&self) -> Option<usize> {
+		self.max_bytes
+	}
+
 	fn get_eviction_policy(&self) -> &EvictionPolicy {
 		&self.eviction_policy
 	}
+
+	fn get_clock(&self) -> &SharedClock {
+		&self.clock
+	}
+
+	fn get_max_protected_size(&self) -> usize {
+		self.max_protected_size
+	}
+
+	fn get_policy_state(&self) -> &BucketPolicyState<K> {
+		&self.policy_state
+	}
+
+	fn get_policy_state_mut(&mut self) -> &mut BucketPolicyState<K> {
+		&mut self.policy_state
+	}
+
+	fn get_ghost_fingerprint(&self, key: &K) -> u64 {
+		hash(key, &self.hash_builder)
+	}
+
+	fn push_item(&mut self, item: Item<K, V>) {
+		self.before_push_item(&item);
+		self.fingerprints.push(get_fingerprint(&item.key, &self.hash_builder));
+		self.items.push(item);
+	}
+
+	fn replace_item(&mut self, position: usize, item: Item<K, V>) -> Item<K, V> {
+		self.fingerprints[position] = get_fingerprint(&item.key, &self.hash_builder);
+		std::mem::replace(&mut self.items[position], item)
+	}
+
+	fn remove_item(&mut self, position: usize) -> Item<K, V> {
+		self.before_remove_item(position);
+		self.fingerprints.remove(position);
+		self.items.remove(position)
+	}
+
+	/// Compares the key only against the items whose fingerprint matches,
+	/// so a miss usually costs a single pass over the fingerprints array.
+	fn get_position(&self, key: &K) -> Option<usize> {
+		let fingerprint = get_fingerprint(key, &self.hash_builder);
+		self
+			.fingerprints
+			.iter()
+			.enumerate()
+			.filter(|(_, &item_fingerprint)| item_fingerprint == fingerprint)
+			.map(|(position, _)| position)
+			.find(|&position| self.items[position].key == *key)
+	}
 }
 
-impl<K, V> Display for DashBucket<K, V>
+impl<K, V, S> Display for DashBucket<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug + Display,
 	V: Eq + Copy + Debug + Display,
+	S: BuildHasher + Clone + Debug,
 {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		for item in &self.items {
@@ -69,9 +255,369 @@ where
 	}
 }
 
-// TODO: implement
-/*
 #[cfg(test)]
 mod tests {
+	use super::*;
+	use crate::hasher::DefaultBuildHasher;
+	use crate::shared::traits::bucket::LFU_AGING_PERIOD;
+	use std::time::{Duration, Instant};
+
+	/// Returns the keys of the evicted items, in eviction order.
+	fn evicted_keys(items: Vec<Item<i64, i64>>) -> Vec<i64> {
+		items.into_iter().map(|item| item.key).collect()
+	}
+
+	fn assert_fingerprints_in_sync(bucket: &DashBucket<i64, i64, DefaultBuildHasher>) {
+		let expected: Vec<u8> = bucket
+			.get_items()
+			.iter()
+			.map(|item| get_fingerprint(&item.key, &bucket.hash_builder))
+			.collect();
+		assert_eq!(bucket.get_fingerprints(), expected.as_slice());
+	}
+
+	#[test]
+	fn test_fingerprints_follow_items() {
+		for eviction_policy in [
+			EvictionPolicy::ClassicLRU,
+			EvictionPolicy::TimestampLRU,
+			EvictionPolicy::Lifo,
+			EvictionPolicy::Lfu,
+			EvictionPolicy::Fifo,
+			EvictionPolicy::Sieve,
+			EvictionPolicy::TwoQ,
+			EvictionPolicy::Slru,
+			EvictionPolicy::Lirs,
+			EvictionPolicy::Clock,
+			EvictionPolicy::ClockPro,
+			EvictionPolicy::LfuAging,
+			EvictionPolicy::Lfuda,
+			EvictionPolicy::LruK { k: 2 },
+			EvictionPolicy::Hyperbolic,
+			EvictionPolicy::Gdsf,
+		] {
+			let mut bucket = DashBucket::new(4, 2, eviction_policy, DefaultBuildHasher::default());
+			for key in 0..10 {
+				bucket.put(Item::new(key, key));
+				bucket.get(&(key / 2));
+				assert_fingerprints_in_sync(&bucket);
+			}
+			bucket.remove(&9);
+			assert_fingerprints_in_sync(&bucket);
+		}
+	}
+
+	#[test]
+	fn test_get_position_with_fingerprints() {
+		let mut bucket = DashBucket::new(8, 0, EvictionPolicy::Fifo, DefaultBuildHasher::default());
+		for key in 0..8 {
+			bucket.put(Item::new(key, key));
+		}
+
+		for key in 0..8 {
+			assert_eq!(bucket.get_position(&key), Some(key as usize));
+		}
+		assert_eq!(bucket.get_position(&100), None);
+	}
+
+	#[test]
+	fn test_sieve_hand_skips_visited_items() {
+		let mut bucket = DashBucket::new(3, 0, EvictionPolicy::Sieve, DefaultBuildHasher::default());
+		for key in 0..3 {
+			bucket.put(Item::new(key, key));
+		}
+		bucket.get(&0);
+
+		// The hand clears the visited bit of 0 and evicts 1, then stays on 2
+		let (_, evicted_items) = bucket.put(Item::new(3, 3));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+		assert!(!bucket.get_items()[0].policy_state.is_visited());
+
+		bucket.get(&3);
+		let (_, evicted_items) = bucket.put(Item::new(4, 4));
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+		assert_eq!(bucket.get_position(&0), Some(0));
+	}
+
+	#[test]
+	fn test_slru_demotes_protected_overflow() {
+		let mut bucket = DashBucket::new(3, 1, EvictionPolicy::Slru, DefaultBuildHasher::default());
+		for key in 0..3 {
+			bucket.put(Item::new(key, key));
+		}
+		bucket.get(&0);
+		bucket.get(&1);
+
+		// 0 was demoted back to the probationary region, as its most recently used item
+		let protected: Vec<i64> = bucket
+			.get_items()
+			.iter()
+			.filter(|item| item.policy_state.is_protected())
+			.map(|item| item.key)
+			.collect();
+		assert_eq!(protected, vec![1]);
+		let (_, evicted_items) = bucket.put(Item::new(3, 3));
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+	}
+
+	#[test]
+	fn test_two_q_remembers_keys_evicted_from_a1in() {
+		let mut bucket = DashBucket::new(2, 1, EvictionPolicy::TwoQ, DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0));
+		bucket.put(Item::new(1, 1));
+		let (_, evicted_items) = bucket.put(Item::new(2, 2));
+		assert_eq!(evicted_keys(evicted_items), vec![0]);
+		assert_eq!(bucket.get_policy_state().get_ghost().unwrap().len(), 1);
+		assert!(bucket
+			.get_policy_state()
+			.get_ghost()
+			.unwrap()
+			.contains(bucket.get_ghost_fingerprint(&0)));
+
+		// 0 comes back straight into Am
+		let (item, evicted_items) = bucket.put(Item::new(0, 0));
+		assert!(item.policy_state.is_protected());
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+		assert_eq!(bucket.get_policy_state().get_ghost().unwrap().len(), 1);
+		assert!(bucket
+			.get_policy_state()
+			.get_ghost()
+			.unwrap()
+			.contains(bucket.get_ghost_fingerprint(&1)));
+	}
+
+	#[test]
+	fn test_lirs_promotes_non_resident_hir_keys() {
+		let mut bucket = DashBucket::new(3, 0, EvictionPolicy::Lirs, DefaultBuildHasher::default());
+		for key in 0..3 {
+			bucket.put(Item::new(key, key));
+		}
+		// Two LIR items and one resident HIR item, which is the one evicted
+		let (_, evicted_items) = bucket.put(Item::new(3, 3));
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+
+		// 2 is still in S, so it comes back as LIR and the bottom LIR item 0 becomes HIR
+		let (item, evicted_items) = bucket.put(Item::new(2, 2));
+		assert!(item.policy_state.is_lir());
+		assert_eq!(evicted_keys(evicted_items), vec![3]);
+		let (_, evicted_items) = bucket.put(Item::new(4, 4));
+		assert_eq!(evicted_keys(evicted_items), vec![0]);
+	}
+
+	#[test]
+	fn test_lfu_breaks_ties_by_last_access() {
+		let mut bucket = DashBucket::new(3, 0, EvictionPolicy::Lfu, DefaultBuildHasher::default());
+		for key in 0..3 {
+			bucket.put(Item::new(key, key));
+		}
+		bucket.get(&1);
+		bucket.get(&0);
+
+		// 0 and 1 have the same count, 1 was accessed longer ago
+		let (_, evicted_items) = bucket.put(Item::new(3, 3));
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+		let (_, evicted_items) = bucket.put(Item::new(4, 4));
+		assert_eq!(evicted_keys(evicted_items), vec![3]);
+		bucket.get(&4);
+		let (_, evicted_items) = bucket.put(Item::new(5, 5));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+	}
+
+	#[test]
+	fn test_lfu_aging_halves_counters() {
+		let mut bucket = DashBucket::new(2, 0, EvictionPolicy::LfuAging, DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0));
+		bucket.put(Item::new(1, 1));
+		// Both puts count as accesses, the last hit of the period halves the counters
+		let period_hits = 2 * LFU_AGING_PERIOD - 2;
+		for _ in 0..period_hits - 1 {
+			bucket.get(&0);
+		}
+		assert_eq!(bucket.get_items()[0].lfu_counter, period_hits - 1);
+
+		bucket.get(&0);
+		assert_eq!(bucket.get_items()[0].lfu_counter, period_hits / 2);
+	}
+
+	#[test]
+	fn test_lfuda_ages_out_old_heavy_hitters() {
+		let mut bucket = DashBucket::new(2, 0, EvictionPolicy::Lfuda, DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0));
+		for _ in 0..3 {
+			bucket.get(&0);
+		}
+
+		// Every eviction raises the cache age, new items end up with a higher priority than 0
+		for key in 1..5 {
+			let (_, evicted_items) = bucket.put(Item::new(key, key));
+			if key > 1 {
+				assert_eq!(evicted_keys(evicted_items), vec![key - 1]);
+			}
+		}
+		let (_, evicted_items) = bucket.put(Item::new(5, 5));
+		assert_eq!(evicted_keys(evicted_items), vec![0]);
+	}
+
+	#[test]
+	fn test_lru_k_retains_history_of_evicted_keys() {
+		let mut bucket = DashBucket::new(2, 0, EvictionPolicy::LruK { k: 2 }, DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0));
+		bucket.put(Item::new(1, 1));
+		bucket.get(&0);
+
+		// 1 was accessed once, so its second most recent access is infinitely far
+		let (_, evicted_items) = bucket.put(Item::new(2, 2));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+		assert_eq!(bucket.get_policy_state().get_retained_history().unwrap().len(), 1);
+
+		// 1 comes back with its retained history and now has two accesses, unlike 2
+		let (item, evicted_items) = bucket.put(Item::new(1, 1));
+		assert_eq!(item.policy_state.get_history().map(|history| history.len()), Some(2));
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+		assert!(bucket
+			.get_policy_state()
+			.get_retained_history()
+			.unwrap()
+			.iter()
+			.all(|(key, _)| *key == 2));
+	}
+
+	#[test]
+	fn test_lru_k_with_custom_k() {
+		let mut bucket = DashBucket::new(2, 0, EvictionPolicy::lru_k(3), DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0));
+		bucket.get(&0);
+		bucket.get(&0);
+		bucket.put(Item::new(1, 1));
+		bucket.get(&1);
+
+		// 1 was accessed twice, fewer than K times, even though it is the most recently used
+		let (_, evicted_items) = bucket.put(Item::new(2, 2));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+		assert_eq!(EvictionPolicy::lru_k(0), EvictionPolicy::LruK { k: 1 });
+	}
+
+	#[test]
+	fn test_hyperbolic_evicts_lowest_access_rate() {
+		let mut bucket = DashBucket::new(2, 0, EvictionPolicy::Hyperbolic, DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0));
+		bucket.put(Item::new(1, 1));
+		for _ in 0..4 {
+			bucket.get(&0);
+		}
+		// 0 was accessed 5 times in 10 seconds, 1 once in 1 second
+		let now = Instant::now();
+		bucket.get_items_mut()[0].inserted_at = now - Duration::from_secs(10);
+		bucket.get_items_mut()[1].inserted_at = now - Duration::from_secs(1);
+
+		let (_, evicted_items) = bucket.put(Item::new(2, 2));
+		assert_eq!(evicted_keys(evicted_items), vec![0]);
+	}
+
+	#[test]
+	fn test_gdsf_evicts_large_items_first() {
+		let mut bucket = DashBucket::new(3, 0, EvictionPolicy::Gdsf, DefaultBuildHasher::default());
+		bucket.put(Item::new(0, 0).with_size(100));
+		bucket.put(Item::new(1, 1).with_size(1));
+		bucket.put(Item::new(2, 2).with_size(1));
+		bucket.get(&0);
+
+		// 0 was accessed twice but is 100 times larger than 1 and 2
+		let (_, evicted_items) = bucket.put(Item::new(3, 3).with_size(1));
+		assert_eq!(evicted_keys(evicted_items), vec![0]);
+		// The cache age is the priority of 0, so 3 outranks the older items that were never hit
+		assert!(bucket.get_policy_state().get_age() > 0.0);
+		let (_, evicted_items) = bucket.put(Item::new(4, 4).with_size(1));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+	}
+
+	#[test]
+	fn test_bucket_capacity_in_bytes() {
+		let mut bucket =
+			DashBucket::new(8, 0, EvictionPolicy::ClassicLRU, DefaultBuildHasher::default()).with_max_bytes(Some(10));
+		for key in 0..4 {
+			bucket.put(Item::new(key, key).with_size(2));
+		}
+		assert_eq!(bucket.used_bytes(), 8);
+
+		// A large item evicts as many of the least recently used items as needed
+		let (_, evicted_items) = bucket.put(Item::new(4, 4).with_size(6));
+		assert_eq!(evicted_keys(evicted_items), vec![0, 1]);
+		let keys: Vec<i64> = bucket.get_items().iter().map(|item| item.key).collect();
+		assert_eq!(keys, vec![2, 3, 4]);
+		assert_eq!(bucket.used_bytes(), 10);
+		assert_fingerprints_in_sync(&bucket);
+	}
+
+	#[test]
+	fn test_clock_replaces_victim_in_place() {
+		let mut bucket = DashBucket::new(3, 0, EvictionPolicy::Clock, DefaultBuildHasher::default());
+		for key in 0..3 {
+			bucket.put(Item::new(key, key));
+		}
+		bucket.get(&0);
+
+		// 0 gets a second chance, 1 is replaced and no other item moves
+		let (_, evicted_items) = bucket.put(Item::new(3, 3));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+		let keys: Vec<i64> = bucket.get_items().iter().map(|item| item.key).collect();
+		assert_eq!(keys, vec![0, 3, 2]);
+		assert_fingerprints_in_sync(&bucket);
+
+		let (_, evicted_items) = bucket.put(Item::new(4, 4));
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+	}
+
+	#[test]
+	fn test_clock_pro_promotes_keys_reused_during_test_period() {
+		let mut bucket = DashBucket::new(3, 1, EvictionPolicy::ClockPro, DefaultBuildHasher::default());
+		for key in 0..3 {
+			bucket.put(Item::new(key, key));
+		}
+		bucket.get(&0);
+
+		// The referenced cold item 0 becomes hot, 1 is evicted and starts its test period
+		let (_, evicted_items) = bucket.put(Item::new(3, 3));
+		assert_eq!(evicted_keys(evicted_items), vec![1]);
+		assert!(bucket.get_items()[0].policy_state.is_protected());
+
+		// 1 comes back hot, 0 is demoted to keep a single hot item
+		let (item, evicted_items) = bucket.put(Item::new(1, 1));
+		assert!(item.policy_state.is_protected());
+		assert_eq!(evicted_keys(evicted_items), vec![2]);
+		assert!(!bucket.get_items()[0].policy_state.is_protected());
+	}
+
+	#[test]
+	fn test_clock_pro_single_slot_bucket() {
+		let mut bucket = DashBucket::new(1, 1, EvictionPolicy::ClockPro, DefaultBuildHasher::default());
+		for key in [0, 1, 0, 1] {
+			bucket.put(Item::new(key, key));
+			bucket.get(&key);
+		}
+		assert_eq!(bucket.size(), 1);
+	}
+
+	#[test]
+	fn test_clock_pro_remembers_keys_evicted_for_bytes() {
+		let mut bucket =
+			DashBucket::new(3, 1, EvictionPolicy::ClockPro, DefaultBuildHasher::default()).with_max_bytes(Some(3));
+		for key in 0..3 {
+			bucket.put(Item::new(key, key).with_size(1));
+		}
+
+		// Evicted through `evict_item`, 0 still starts its test period and comes back hot
+		let (_, evicted_items) = bucket.put(Item::new(3, 3).with_size(1));
+		assert_eq!(evicted_keys(evicted_items), vec![0]);
+		let (item, _) = bucket.put(Item::new(0, 0).with_size(1));
+		assert!(item.policy_state.is_protected());
+	}
+
+	#[test]
+	fn test_clock_zero_slot_bucket() {
+		for eviction_policy in [EvictionPolicy::Clock, EvictionPolicy::ClockPro] {
+			let mut bucket = DashBucket::new(0, 0, eviction_policy, DefaultBuildHasher::default());
+			assert_eq!(bucket.put(Item::new(0, 0)).1, vec![]);
+		}
+	}
 }
-*/
@@ -1,19 +1,26 @@
-use super::dash_settings::DashSettings;
+use super::dash_settings::{AdmissionPolicy, DashSettings, InsertMode};
 use super::Dash;
+use crate::clock::{clock_from_usize, real_clock, SharedClock};
 use crate::eviction_policy::EvictionPolicy;
+use crate::hasher::HasherKind;
 
 use chrono::Local;
-use jni::{objects::JClass, sys::jlong, JNIEnv};
-use log::info;
+use jni::{
+	objects::JClass,
+	sys::{jboolean, jdouble, jlong},
+	JNIEnv,
+};
+use log::{error, info};
 use simplelog::*;
 use std::fs::{create_dir_all, File};
 use std::sync::Once;
+use std::time::Duration;
 
-type DashTy = Dash<i64, i64>;
+type DashTy = Dash<i64, i64, HasherKind>;
 
 static INIT: Once = Once::new();
 
-fn init_logger(debug_mode: usize) {
+pub(crate) fn init_logger(debug_mode: usize) {
 	INIT.call_once(|| {
 		// Get the current time and format it
 		let current_time = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
@@ -48,12 +55,184 @@ pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_
 	_env: JNIEnv<'local>,
 	_class: JClass<'local>,
 ) -> jlong {
-	create_cache(DashSettings::default())
+	create_cache(DashSettings::default(), HasherKind::default(), real_clock())
 }
 
 #[no_mangle]
 pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCache<
 	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	num_of_segments: jlong,
+	num_of_normal_buckets: jlong,
+	num_of_stash_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	debug_mode: jlong,
+) -> jlong {
+	// The hasher with id 0 is the default one, the seed is ignored
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithHasher(
+		env,
+		class,
+		num_of_segments,
+		num_of_normal_buckets,
+		num_of_stash_buckets,
+		bucket_size,
+		eviction_policy,
+		debug_mode,
+		0,
+		0,
+	)
+}
+
+/// Like `initCache`, hashing keys with the hasher `HasherKind::from_usize` gives for `hasher` and `hasher_seed`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithHasher<
+	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	num_of_segments: jlong,
+	num_of_normal_buckets: jlong,
+	num_of_stash_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	debug_mode: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+) -> jlong {
+	// The admission policy with id 0 admits every key
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithAdmissionPolicy(
+		env,
+		class,
+		num_of_segments,
+		num_of_normal_buckets,
+		num_of_stash_buckets,
+		bucket_size,
+		eviction_policy,
+		debug_mode,
+		hasher,
+		hasher_seed,
+		0,
+	)
+}
+
+/// Like `initCacheWithHasher`, filtering new keys with the admission policy `AdmissionPolicy::from_usize` gives for `admission_policy`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithAdmissionPolicy<
+	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	num_of_segments: jlong,
+	num_of_normal_buckets: jlong,
+	num_of_stash_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	debug_mode: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+	admission_policy: jlong,
+) -> jlong {
+	// The insert mode with id 1 is the balanced insert, the default one
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithInsertMode(
+		env,
+		class,
+		num_of_segments,
+		num_of_normal_buckets,
+		num_of_stash_buckets,
+		bucket_size,
+		eviction_policy,
+		debug_mode,
+		hasher,
+		hasher_seed,
+		admission_policy,
+		1,
+	)
+}
+
+/// Like `initCacheWithAdmissionPolicy`, placing new items with the insert mode `InsertMode::from_usize` gives for `insert_mode`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithInsertMode<
+	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	num_of_segments: jlong,
+	num_of_normal_buckets: jlong,
+	num_of_stash_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	debug_mode: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+	admission_policy: jlong,
+	insert_mode: jlong,
+) -> jlong {
+	// A bucket capacity of 0 bytes only limits the number of items
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithBucketCapacityBytes(
+		env,
+		class,
+		num_of_segments,
+		num_of_normal_buckets,
+		num_of_stash_buckets,
+		bucket_size,
+		eviction_policy,
+		debug_mode,
+		hasher,
+		hasher_seed,
+		admission_policy,
+		insert_mode,
+		0,
+	)
+}
+
+/// Like `initCacheWithInsertMode`, limiting every bucket to `bucket_capacity_bytes` bytes on top of `bucket_size` items,
+/// 0 to only limit the number of items. The items take the size given to `putToCacheWithSize`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithBucketCapacityBytes<
+	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	num_of_segments: jlong,
+	num_of_normal_buckets: jlong,
+	num_of_stash_buckets: jlong,
+	bucket_size: jlong,
+	eviction_policy: jlong,
+	debug_mode: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+	admission_policy: jlong,
+	insert_mode: jlong,
+	bucket_capacity_bytes: jlong,
+) -> jlong {
+	// The wall clock, like a cache created without a clock
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithClock(
+		env,
+		class,
+		num_of_segments,
+		num_of_normal_buckets,
+		num_of_stash_buckets,
+		bucket_size,
+		eviction_policy,
+		debug_mode,
+		hasher,
+		hasher_seed,
+		admission_policy,
+		insert_mode,
+		bucket_capacity_bytes,
+		0,
+		0,
+	)
+}
+
+/// Like `initCacheWithBucketCapacityBytes`, taking the time from the clock `clock_from_usize` gives for `clock`.
+/// A virtual clock advances by `clock_tick_millis` on every request, or only through `advanceClock` if it is 0.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_initCacheWithClock<
+	'local,
 >(
 	_env: JNIEnv<'local>,
 	_class: JClass<'local>,
@@ -63,28 +242,67 @@ pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_
 	bucket_size: jlong,
 	eviction_policy: jlong,
 	debug_mode: jlong,
+	hasher: jlong,
+	hasher_seed: jlong,
+	admission_policy: jlong,
+	insert_mode: jlong,
+	bucket_capacity_bytes: jlong,
+	clock: jlong,
+	clock_tick_millis: jlong,
 ) -> jlong {
+	init_logger(debug_mode as usize);
+	// Unknown ids are refused with a null pointer instead of unwinding into the JVM
+	let Some(eviction_policy) = EvictionPolicy::from_usize(eviction_policy as usize) else {
+		error!("new - unknown eviction policy {}", eviction_policy);
+		return 0;
+	};
+	let Some(admission_policy) = AdmissionPolicy::from_usize(admission_policy as usize) else {
+		error!("new - unknown admission policy {}", admission_policy);
+		return 0;
+	};
+	let Some(insert_mode) = InsertMode::from_usize(insert_mode as usize) else {
+		error!("new - unknown insert mode {}", insert_mode);
+		return 0;
+	};
+	let Some(hash_builder) = HasherKind::from_usize(hasher as usize, hasher_seed as u64) else {
+		error!("new - unknown hasher {}", hasher);
+		return 0;
+	};
+	let Some(clock) = clock_from_usize(clock as usize, Duration::from_millis(clock_tick_millis.max(0) as u64)) else {
+		error!("new - unknown clock {}", clock);
+		return 0;
+	};
 	let settings = DashSettings {
 		num_of_segments: num_of_segments as usize,
 		num_of_normal_buckets: num_of_normal_buckets as usize,
 		num_of_stash_buckets: num_of_stash_buckets as usize,
 		bucket_size: bucket_size as usize,
-		eviction_policy: EvictionPolicy::from_usize(eviction_policy as usize).unwrap(),
+		eviction_policy,
+		admission_policy,
+		insert_mode,
+		bucket_capacity_bytes: (bucket_capacity_bytes > 0).then_some(bucket_capacity_bytes as usize),
 		debug_mode: debug_mode as usize,
+		..DashSettings::default()
 	};
 
-	create_cache(settings)
+	create_cache(settings, hash_builder, clock)
 }
 
-fn create_cache(settings: DashSettings) -> jlong {
+fn create_cache(settings: DashSettings, hash_builder: HasherKind, clock: SharedClock) -> jlong {
 	init_logger(settings.debug_mode);
 
-	let cache: DashTy = Dash::new(settings.clone());
+	let cache: DashTy = match Dash::with_clock(settings.clone(), hash_builder, clock) {
+		Ok(cache) => cache,
+		Err(unsupported) => {
+			error!("new - {}", unsupported);
+			return 0;
+		}
+	};
 	let cache_ptr = Box::into_raw(Box::new(cache)) as jlong;
 
 	info!(
-		"new - Cache Ptr: {}, initializing cache. Settings: {:?}",
-		cache_ptr, settings
+		"new - Cache Ptr: {}, initializing cache. Settings: {:?}, Hasher: {:?}",
+		cache_ptr, settings, hash_builder
 	);
 	cache_ptr
 }
@@ -115,16 +333,144 @@ pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_
 #[no_mangle]
 pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_putToCache<
 	'local,
+>(
+	env: JNIEnv<'local>,
+	class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+) {
+	Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_upsertToCache(
+		env, class, cache_ptr, key, value,
+	);
+}
+
+/// Like `putToCache`, returns whether the key already existed and its value was replaced.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_upsertToCache<
+	'local,
 >(
 	_env: JNIEnv<'local>,
 	_class: JClass<'local>,
 	cache_ptr: jlong,
 	key: jlong,
 	value: jlong,
-) {
+) -> jboolean {
+	let cache = unsafe { &mut *(cache_ptr as *mut DashTy) };
+	let (previous_value, evicted) = cache.put(key, value);
+	info!(
+		"put - Cache Ptr: {}, Key: {}, Value: {}, Previous Value: {:?}, Evicted: {:?}",
+		cache_ptr, key, value, previous_value, evicted
+	);
+	previous_value.is_some() as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_putToCacheWithSize<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+	size: jlong,
+) -> jboolean {
+	let cache = unsafe { &mut *(cache_ptr as *mut DashTy) };
+	let (previous_value, evicted) = cache.put_with_size(key, value, size.max(0) as usize);
+	info!(
+		"put_with_size - Cache Ptr: {}, Key: {}, Value: {}, Size: {}, Previous Value: {:?}, Evicted: {:?}",
+		cache_ptr, key, value, size, previous_value, evicted
+	);
+	previous_value.is_some() as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_putToCacheWithTtl<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+	value: jlong,
+	ttl_millis: jlong,
+) -> jboolean {
+	let cache = unsafe { &mut *(cache_ptr as *mut DashTy) };
+	let (previous_value, evicted) = cache.put_with_ttl(key, value, Duration::from_millis(ttl_millis.max(0) as u64));
+	info!(
+		"put_with_ttl - Cache Ptr: {}, Key: {}, Value: {}, TTL: {}ms, Previous Value: {:?}, Evicted: {:?}",
+		cache_ptr, key, value, ttl_millis, previous_value, evicted
+	);
+	previous_value.is_some() as jboolean
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_removeFromCache<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	key: jlong,
+) -> jlong {
 	let cache = unsafe { &mut *(cache_ptr as *mut DashTy) };
-	cache.put(key as i64, value as i64);
-	info!("put - Cache Ptr: {}, Key: {}, Value: {}", cache_ptr, key, value);
+	let res = cache.remove(&key);
+	match res {
+		None => {
+			info!("remove - Cache Ptr: {}, Key: {}, not found", cache_ptr, key);
+			-1
+		}
+		Some(value) => {
+			info!("remove - Cache Ptr: {}, Key: {}, removed", cache_ptr, key);
+			value
+		}
+	}
+}
+
+/// Moves a virtual clock to `elapsed_millis` after the cache was created, e.g. to the timestamp of a trace request.
+/// Does nothing with the wall clock.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_advanceClock<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+	elapsed_millis: jlong,
+) {
+	let cache = unsafe { &*(cache_ptr as *const DashTy) };
+	cache.advance_clock(Duration::from_millis(elapsed_millis.max(0) as u64));
+	info!(
+		"advance_clock - Cache Ptr: {}, Elapsed: {}ms",
+		cache_ptr, elapsed_millis
+	);
+}
+
+/// Returns the share of the lookups that were hits, see `Dash::hit_ratio`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_getObjectHitRatio<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+) -> jdouble {
+	let cache = unsafe { &*(cache_ptr as *const DashTy) };
+	cache.hit_ratio().object_hit_ratio()
+}
+
+/// Returns the share of the looked up bytes that were hits, see `Dash::hit_ratio`.
+#[no_mangle]
+pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_dash_DashRustPolicy_getByteHitRatio<
+	'local,
+>(
+	_env: JNIEnv<'local>,
+	_class: JClass<'local>,
+	cache_ptr: jlong,
+) -> jdouble {
+	let cache = unsafe { &*(cache_ptr as *const DashTy) };
+	cache.hit_ratio().byte_hit_ratio()
 }
 
 #[no_mangle]
@@ -136,5 +482,10 @@ pub extern "system" fn Java_com_github_benmanes_caffeine_cache_simulator_policy_
 	cache_ptr: jlong,
 ) {
 	let _boxed_cache = unsafe { Box::from_raw(cache_ptr as *mut DashTy) };
-	info!("drop_cache - Cache Ptr: {}", cache_ptr);
+	info!(
+		"drop_cache - Cache Ptr: {}, {}, Ghost Memory: {} bytes",
+		cache_ptr,
+		_boxed_cache.hit_ratio(),
+		_boxed_cache.ghost_memory_usage()
+	);
 }
@@ -0,0 +1,100 @@
+//! Redis-style sampled eviction.
+//!
+//! Every eviction round samples a few random items and merges them into a small pool of the best victims
+//! seen so far. The pool outlives the round, so the victims found by previous rounds are still considered
+//! and the approximation gets closer to evicting the worst item of the whole segment or Dash.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+
+#[derive(Debug)]
+pub struct EvictionPool<K> {
+	/// Keys of the eviction candidates, best victim first.
+	keys: Vec<K>,
+	/// Maximum number of keys in the pool.
+	pool_size: usize,
+	/// Number of items sampled every round.
+	samples: usize,
+	rng: StdRng,
+}
+
+impl<K> EvictionPool<K>
+where
+	K: Eq + Copy,
+{
+	pub fn new(samples: usize, pool_size: usize, seed: u64) -> Self {
+		EvictionPool {
+			keys: Vec::with_capacity(pool_size + samples),
+			pool_size: pool_size.max(1),
+			samples: samples.max(1),
+			rng: StdRng::seed_from_u64(seed),
+		}
+	}
+
+	/// Runs a sampling round and removes the best victim `is_evictable` accepts from the pool.
+	///
+	/// `sample` draws a random key given the RNG, or `None` if it hit an empty slot.
+	/// Keys that are no longer resident are dropped from the pool, the others are ranked again by `compare`,
+	/// which orders two resident keys like `EvictionPolicy::compare_victims`.
+	pub fn take_victim(
+		&mut self,
+		mut sample: impl FnMut(&mut StdRng) -> Option<K>,
+		is_resident: impl Fn(&K) -> bool,
+		compare: impl Fn(&K, &K) -> Ordering,
+		is_evictable: impl Fn(&K) -> bool,
+	) -> Option<K> {
+		// Candidates may have been removed or evicted since the previous round
+		self.keys.retain(|key| is_resident(key));
+		for _ in 0..self.samples {
+			if let Some(key) = sample(&mut self.rng) {
+				if !self.keys.contains(&key) {
+					self.keys.push(key);
+				}
+			}
+		}
+		// Candidates accessed since the previous round are ranked again
+		self.keys.sort_by(|a, b| compare(a, b));
+		self.keys.truncate(self.pool_size);
+
+		let position = self.keys.iter().position(is_evictable)?;
+		Some(self.keys.remove(position))
+	}
+
+	/// Returns a pool with the same settings for a new segment, seeded from this pool's RNG.
+	pub fn fork(&mut self) -> Self {
+		EvictionPool::new(self.samples, self.pool_size, self.rng.gen())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_pool_keeps_best_victims_between_rounds() {
+		let mut pool = EvictionPool::new(4, 2, 7);
+		let population: Vec<i64> = (0..100).collect();
+		let sample = |rng: &mut StdRng| Some(population[rng.gen_range(0..population.len())]);
+
+		let mut victims = Vec::new();
+		for _ in 0..10 {
+			victims.push(pool.take_victim(sample, |_| true, |a, b| a.cmp(b), |_| true).unwrap());
+		}
+		// The same seed gives the same victims
+		let mut same_pool = EvictionPool::new(4, 2, 7);
+		let same_victims: Vec<i64> = (0..10)
+			.map(|_| {
+				same_pool
+					.take_victim(sample, |_| true, |a, b| a.cmp(b), |_| true)
+					.unwrap()
+			})
+			.collect();
+		assert_eq!(victims, same_victims);
+
+		// The pool only ever holds keys better than the last ones sampled
+		assert!(pool.keys.len() <= 2);
+		let skipped = pool.take_victim(sample, |_| true, |a, b| a.cmp(b), |key| key % 2 == 1);
+		assert!(skipped.is_none_or(|key| key % 2 == 1));
+	}
+}
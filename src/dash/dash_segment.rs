@@ -1,74 +1,214 @@
+use super::dash_arc::{ArcGhostHit, ArcState};
 use super::dash_bucket::DashBucket;
-use super::dash_settings::DashSettings;
+use super::dash_sampling::EvictionPool;
+use super::dash_settings::{DashMode, DashSettings, EvictionScope, InsertMode};
+use crate::clock::{real_clock, SharedClock};
+use crate::eviction_policy::EvictionPolicy;
+use crate::shared::ghost_history::GhostHistory;
+use crate::shared::hash_layout::HashLayout;
 use crate::shared::item::Item;
 use crate::shared::traits::bucket::Bucket;
-use crate::shared::utils::get_index;
+use crate::shared::utils::{get_fingerprint, get_protected_size, hash};
+use rand::rngs::StdRng;
+use rand::Rng;
 use std::fmt::{Debug, Display};
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
+use std::time::Instant;
+
+/// Highest access frequency S3-FIFO keeps per item, in `Item::lfu_counter`.
+const S3_FIFO_MAX_FREQUENCY: usize = 3;
 
 #[derive(Debug)]
-pub struct DashSegment<K, V>
+pub struct DashSegment<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
-	pub buckets: Vec<DashBucket<K, V>>,
+	pub buckets: Vec<DashBucket<K, V, S>>,
 	pub segment_size: usize,
-	pub stash_buckets: Vec<DashBucket<K, V>>,
+	pub stash_buckets: Vec<DashBucket<K, V, S>>,
 	pub stash_size: usize,
+	/// Number of most significant hash bits shared by all the keys in this segment (Dash-EH local depth).
+	pub local_depth: usize,
+	pub layout: HashLayout,
+	pub mode: DashMode,
+	pub insert_mode: InsertMode,
+	pub eviction_scope: EvictionScope,
+	/// Hashes of the keys S3-FIFO evicted from the small FIFO, oldest first.
+	ghost: GhostHistory,
+	/// Maximum number of items in the protected region of SLRU and 2Q with `EvictionScope::Segment`.
+	max_protected_size: usize,
+	/// Set with `EvictionPolicy::Arc`, the ghost lists and adaptive target of the segment.
+	arc: Option<ArcState>,
+	/// Set with `EvictionScope::SampledSegment`, the eviction candidates sampled from the segment.
+	eviction_pool: Option<EvictionPool<K>>,
+	clock: SharedClock,
+	hash_builder: S,
 }
 
 // TODO: use the Segment trait
-impl<K, V> DashSegment<K, V>
+impl<K, V, S> DashSegment<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug,
 	V: Eq + Copy + Debug,
+	S: BuildHasher + Clone + Debug,
 {
-	pub fn new(settings: DashSettings) -> Self {
-		let mut buckets: Vec<DashBucket<K, V>> = Vec::new();
+	/// Creates an empty segment, the settings are the ones `Dash::with_clock` validated.
+	pub fn new(settings: DashSettings, local_depth: usize, hash_builder: S) -> Self {
+		let eviction_policy = match settings.insert_mode {
+			InsertMode::S3Fifo => EvictionPolicy::Fifo,
+			_ => settings.eviction_policy.clone(),
+		};
+		// With a segment scope the protected region is limited across the segment rather than per bucket
+		let capacity = (settings.num_of_normal_buckets + settings.num_of_stash_buckets) * settings.bucket_size;
+		let max_protected_size = get_protected_size(capacity, settings.protected_fraction);
+		let bucket_max_protected_size = match settings.eviction_scope {
+			EvictionScope::Segment => settings.bucket_size,
+			_ => get_protected_size(settings.bucket_size, settings.protected_fraction),
+		};
+		let mut buckets: Vec<DashBucket<K, V, S>> = Vec::new();
 		for _ in 0..settings.num_of_normal_buckets {
 			// TODO: pass the settings as a reference
-			buckets.push(DashBucket::new(settings.bucket_size, settings.eviction_policy.clone()));
+			buckets.push(
+				DashBucket::new(
+					settings.bucket_size,
+					bucket_max_protected_size,
+					eviction_policy.clone(),
+					hash_builder.clone(),
+				)
+				.with_max_bytes(settings.bucket_capacity_bytes)
+				.with_ghost_capacity(settings.ghost_capacity),
+			);
 		}
 
-		let mut stash_buckets: Vec<DashBucket<K, V>> = Vec::new();
+		let mut stash_buckets: Vec<DashBucket<K, V, S>> = Vec::new();
 		for _ in 0..settings.num_of_stash_buckets {
-			stash_buckets.push(DashBucket::new(settings.bucket_size, settings.eviction_policy.clone()));
+			stash_buckets.push(
+				DashBucket::new(
+					settings.bucket_size,
+					bucket_max_protected_size,
+					eviction_policy.clone(),
+					hash_builder.clone(),
+				)
+				.with_max_bytes(settings.bucket_capacity_bytes)
+				.with_ghost_capacity(settings.ghost_capacity),
+			);
 		}
+		let arc = match eviction_policy {
+			EvictionPolicy::Arc => Some(ArcState::new(capacity, settings.ghost_capacity.unwrap_or(capacity))),
+			_ => None,
+		};
+		// S3-FIFO remembers as many keys as the main FIFO holds, the other policies remember keys per bucket
+		let ghost_capacity = match settings.insert_mode {
+			InsertMode::S3Fifo => settings
+				.ghost_capacity
+				.unwrap_or(settings.num_of_normal_buckets * settings.bucket_size),
+			_ => 0,
+		};
+		let eviction_pool = match settings.eviction_scope {
+			EvictionScope::SampledSegment => Some(EvictionPool::new(
+				settings.eviction_samples,
+				settings.eviction_pool_size,
+				settings.sampling_seed,
+			)),
+			_ => None,
+		};
 		DashSegment {
 			buckets,
 			stash_buckets,
 			segment_size: settings.num_of_normal_buckets,
 			stash_size: settings.num_of_stash_buckets,
+			local_depth,
+			layout: HashLayout::new(
+				settings.num_of_segments,
+				settings.num_of_normal_buckets,
+				settings.num_of_stash_buckets,
+			),
+			mode: settings.mode,
+			insert_mode: settings.insert_mode,
+			eviction_scope: settings.eviction_scope,
+			ghost: GhostHistory::new(ghost_capacity),
+			max_protected_size,
+			arc,
+			eviction_pool,
+			clock: real_clock(),
+			hash_builder,
 		}
 	}
 
+	/// Takes the timestamps of the items from `clock` rather than from the wall clock, in every bucket of the segment.
+	pub fn with_clock(mut self, clock: SharedClock) -> Self {
+		self.buckets = self
+			.buckets
+			.into_iter()
+			.map(|bucket| bucket.with_clock(clock.clone()))
+			.collect();
+		self.stash_buckets = self
+			.stash_buckets
+			.into_iter()
+			.map(|bucket| bucket.with_clock(clock.clone()))
+			.collect();
+		self.clock = clock;
+		self
+	}
+
 	// TODO: could be written better
 	/// Returns a reference to the item with `key`.
 	///
 	/// As a side effect makes updates according to the eviction policy.
 	pub fn get(&mut self, key: &K) -> Option<&Item<K, V>> {
-		let stash_bucket_index = get_index(key, self.stash_size);
-		let stash_bucket = &self.stash_buckets[stash_bucket_index];
-		let target_bucket_index = get_index(key, self.segment_size);
+		if self.is_s3_fifo() {
+			// S3-FIFO never moves items on a hit, it only counts the access
+			let (is_stash, bucket_index, position) = self.locate(key)?;
+			let bucket = self.get_bucket_mut(is_stash, bucket_index);
+			let item = &mut bucket.get_items_mut()[position];
+			item.lfu_counter = (item.lfu_counter + 1).min(S3_FIFO_MAX_FREQUENCY);
+			return Some(&bucket.get_items()[position]);
+		}
 
-		// The order assumes that the data is more likely to be in the stash bucket,
-		// this assumption should be tested
-		if let Some(position) = stash_bucket.get_position(key) {
-			// If the key is in the stash bucket, we need to move it to the target bucket
-			let mut_stash_bucket = &mut self.stash_buckets[stash_bucket_index];
-			let data = mut_stash_bucket.get_from_position(position).clone();
-			mut_stash_bucket.remove(key);
+		if self.eviction_scope == EvictionScope::Segment && *self.get_eviction_policy() == EvictionPolicy::Slru {
+			// The bucket promoted the item, the protected region is limited across the segment
+			self.lookup(key)?;
+			self.demote_protected_overflow();
+			let (is_stash, bucket_index, position) = self.locate(key)?;
+			return Some(&self.get_bucket(is_stash, bucket_index).get_items()[position]);
+		}
+
+		self.lookup(key)
+	}
+
+	/// Returns a reference to the item with `key`, promoting it from the stash according to the insert mode.
+	///
+	/// As a side effect makes updates according to the eviction policy.
+	fn lookup(&mut self, key: &K) -> Option<&Item<K, V>> {
+		let target_bucket_index = self.get_target_bucket_index(key);
+
+		// The stash is only scanned when the overflow metadata says the key may be there
+		if let Some((stash_bucket_index, position)) = self.find_in_stash(key) {
+			let promote = match self.insert_mode {
+				InsertMode::StashFirst | InsertMode::S3Fifo => {
+					// A table never evicts, so the item stays in the stash until the target bucket has room
+					self.mode == DashMode::Cache || !self.buckets[target_bucket_index].is_full()
+				}
+				InsertMode::Balanced => false,
+			};
+			if !promote {
+				let mut_stash_bucket = &mut self.stash_buckets[stash_bucket_index];
+				return Some(mut_stash_bucket.get_from_position(position));
+			}
 
-			let mut_target_bucket = &mut self.buckets[target_bucket_index];
-			let (pushed_data, evicted_data) = mut_target_bucket.put(data);
-			if let Some(data) = evicted_data {
-				mut_stash_bucket.put(data);
+			// If the key is in the stash bucket, we need to move it to the target bucket
+			self.stash_buckets[stash_bucket_index].get_from_position(position);
+			// The eviction policy may have moved the item inside the stash bucket
+			let position = self.stash_buckets[stash_bucket_index].get_position(key)?;
+			if !self.promote_from_stash(stash_bucket_index, position, target_bucket_index) {
+				// Nothing may be evicted by a lookup, the item stays where it is
+				return Some(&self.stash_buckets[stash_bucket_index].get_items()[position]);
 			}
-			Some(pushed_data)
+			self.buckets[target_bucket_index].get_items().last()
 		} else {
 			// If the key is not in the stash bucket, we need to check the target bucket
-			let target_bucket_index = get_index(key, self.segment_size);
 			let target_bucket = &self.buckets[target_bucket_index];
 
 			if let Some(position) = target_bucket.get_position(key) {
@@ -77,12 +217,8 @@ where
 				Some(mut_target_bucket.get_from_position(position))
 			} else {
 				// If the key is not in the target bucket, we need to check the probing bucket
-
-				if target_bucket_index == self.buckets.len() - 1 {
-					// If the target bucket is the last bucket, there is not probing bucket
-					return None;
-				}
-				let probing_bucket_index = target_bucket_index + 1;
+				// If the target bucket is the last bucket, there is not probing bucket
+				let probing_bucket_index = self.get_probing_bucket_index(target_bucket_index)?;
 				let probing_bucket = &self.buckets[probing_bucket_index];
 				if let Some(position) = probing_bucket.get_position(key) {
 					// If the key is in the probing bucket, we need to update the position
@@ -96,18 +232,978 @@ where
 		}
 	}
 
-	/// Insert the key, value pair into the segment.
+	/// Moves the item in `position` of stash bucket `stash_bucket_index` to the end of its target bucket.
+	///
+	/// If the target bucket is full, its victim takes the freed stash slot instead of being evicted.
+	/// Both are plain moves, the eviction policy only sees an item leave one bucket and enter the other.
+	/// Returns whether the item was moved, which is not the case if either item does not fit in the bytes
+	/// the other one freed.
+	fn promote_from_stash(&mut self, stash_bucket_index: usize, position: usize, target_bucket_index: usize) -> bool {
+		let target_bucket = &self.buckets[target_bucket_index];
+		let stash_bucket = &self.stash_buckets[stash_bucket_index];
+		let item = &stash_bucket.get_items()[position];
+		if target_bucket.has_room_for(item) {
+			let item = self.remove_from_stash(stash_bucket_index, position);
+			self.buckets[target_bucket_index].push_item(item);
+			return true;
+		}
+
+		let Some(victim_position) = target_bucket.get_victim_position() else {
+			return false;
+		};
+		let victim = &target_bucket.get_items()[victim_position];
+		if !target_bucket.has_room_to_resize(victim_position, item) || !stash_bucket.has_room_to_resize(position, victim) {
+			return false;
+		}
+		let item = self.remove_from_stash(stash_bucket_index, position);
+		let demoted_item = self.buckets[target_bucket_index].remove_item(victim_position);
+		self.buckets[target_bucket_index].push_item(item);
+		self.add_overflow_record(&demoted_item.key, stash_bucket_index);
+		self.stash_buckets[stash_bucket_index].push_item(demoted_item);
+		true
+	}
+
+	/// Replaces the value, size and expiration time of the key of `item` wherever it lives in the segment
+	/// and returns the previous value.
+	///
+	/// The item is given back if its key is not in the segment, or if it no longer fits in its bucket,
+	/// in which case the key is removed and the item keeps what the eviction policy knew about it.
+	/// As a side effect makes updates according to the eviction policy.
+	pub fn update(&mut self, item: Item<K, V>) -> Result<V, Item<K, V>> {
+		// The update is an access like `get`, which may also move the item
+		if self.get(&item.key).is_none() {
+			return Err(item);
+		}
+		let Some((is_stash, bucket_index, position)) = self.locate(&item.key) else {
+			return Err(item);
+		};
+		let bucket = self.get_bucket_mut(is_stash, bucket_index);
+		if !bucket.has_room_to_resize(position, &item) {
+			// The item grew past the room left in its bucket, it has to be put again like a new item,
+			// keeping what the eviction policy knows about it
+			let Some(previous_item) = self.remove(&item.key) else {
+				return Err(item);
+			};
+			return Err(Item {
+				value: item.value,
+				size: item.size,
+				expires_at: item.expires_at,
+				..previous_item
+			});
+		}
+		let existing_item = &mut bucket.get_items_mut()[position];
+		existing_item.size = item.size;
+		existing_item.expires_at = item.expires_at;
+		let previous_value = std::mem::replace(&mut existing_item.value, item.value);
+		if let EvictionPolicy::Gdsf = bucket.get_eviction_policy() {
+			// `get` computed the priority with the previous size
+			let priority = bucket.get_priority(&bucket.get_items()[position]);
+			bucket.get_items_mut()[position].policy_state.set_priority(priority);
+		}
+		Ok(previous_value)
+	}
+
+	/// Removes the item with `key` if it expired at `now`, returns whether it did.
+	pub fn remove_if_expired(&mut self, key: &K, now: Instant) -> bool {
+		let expired = self.peek(key).is_some_and(|item| item.is_expired(now));
+		if expired {
+			self.remove(key);
+		}
+		expired
+	}
+
+	/// Removes the expired items of the buckets `key` may be put into, so they are reclaimed before any live item is evicted.
+	///
+	/// Returns how many items were removed.
+	pub fn remove_expired_items_for(&mut self, key: &K, now: Instant) -> usize {
+		let target_bucket_index = self.get_target_bucket_index(key);
+		let probing_bucket_index = self.get_probing_bucket_index(target_bucket_index);
+		let normal_removed: usize = std::iter::once(target_bucket_index)
+			.chain(probing_bucket_index)
+			.map(|bucket_index| self.buckets[bucket_index].remove_expired_items(now))
+			.sum();
+		let stash_removed: usize = (0..self.stash_size)
+			.map(|stash_bucket_index| self.remove_expired_from_stash(stash_bucket_index, now))
+			.sum();
+		normal_removed + stash_removed
+	}
+
+	/// Removes the expired items of a single bucket, the normal buckets come first and then the stash buckets.
+	///
+	/// Returns how many items were removed.
+	pub fn remove_expired_from_bucket(&mut self, bucket_index: usize, now: Instant) -> usize {
+		if bucket_index < self.segment_size {
+			self.buckets[bucket_index].remove_expired_items(now)
+		} else {
+			self.remove_expired_from_stash(bucket_index - self.segment_size, now)
+		}
+	}
+
+	/// Like `Bucket::remove_expired_items` for a stash bucket, keeping the overflow records in sync.
+	fn remove_expired_from_stash(&mut self, stash_bucket_index: usize, now: Instant) -> usize {
+		let mut removed = 0;
+		for position in (0..self.stash_buckets[stash_bucket_index].size()).rev() {
+			if self.stash_buckets[stash_bucket_index].get_items()[position].is_expired(now) {
+				self.remove_from_stash(stash_bucket_index, position);
+				removed += 1;
+			}
+		}
+		removed
+	}
+
+	/// Returns whether the key lives in a stash bucket, the index of its bucket and its position inside it.
+	fn locate(&self, key: &K) -> Option<(bool, usize, usize)> {
+		if let Some((stash_bucket_index, position)) = self.find_in_stash(key) {
+			return Some((true, stash_bucket_index, position));
+		}
+
+		let target_bucket_index = self.get_target_bucket_index(key);
+		let probing_bucket_index = self.get_probing_bucket_index(target_bucket_index);
+		std::iter::once(target_bucket_index)
+			.chain(probing_bucket_index)
+			.find_map(|index| Some((false, index, self.buckets[index].get_position(key)?)))
+	}
+
+	/// Removes the item with `key` from the segment and returns it.
+	///
+	/// The stash, target and probing buckets are searched.
+	/// The item is removed rather than evicted, so the eviction policy does not remember it, see `evict_at`.
+	pub fn remove(&mut self, key: &K) -> Option<Item<K, V>> {
+		match self.locate(key)? {
+			(true, stash_bucket_index, position) => Some(self.remove_from_stash(stash_bucket_index, position)),
+			(false, bucket_index, position) => Some(self.buckets[bucket_index].remove_item(position)),
+		}
+	}
+
+	/// Insert the key, value pair into the segment, evicting items if there is no room for it.
+	/// This function assumes that the key is not already in the segment.
+	///
+	/// Returns the evicted items, in eviction order. A capacity in bytes may take several of them to fit a large item.
+	pub fn put(&mut self, mut item: Item<K, V>) -> Vec<Item<K, V>> {
+		match (self.eviction_scope, self.insert_mode) {
+			(_, InsertMode::S3Fifo) => self.put_s3_fifo(item),
+			_ if self.arc.is_some() => self.put_arc(item),
+			(EvictionScope::Bucket, InsertMode::StashFirst) => {
+				self.put_into_stash(self.get_stash_bucket_index(&item.key), item)
+			}
+			(EvictionScope::Bucket, InsertMode::Balanced) => {
+				let Err(item) = self.try_put(item) else {
+					return Vec::new();
+				};
+				// Every candidate bucket is full, the target bucket evicts according to the eviction policy
+				let target_bucket_index = self.get_target_bucket_index(&item.key);
+				self.buckets[target_bucket_index].put(item).1
+			}
+			(EvictionScope::Segment, _) => {
+				self.recall_evicted_key(&mut item);
+				self.put_evicting(item, |segment, key| segment.evict_from_candidates(key))
+			}
+			(EvictionScope::SampledSegment | EvictionScope::SampledSharedPool, _) => {
+				self.recall_evicted_key(&mut item);
+				// Without a sampled victim that frees a slot for the key, the candidate buckets evict
+				self.put_evicting(item, |segment, key| {
+					segment
+						.evict_sampled(key)
+						.or_else(|| segment.evict_from_candidates(key))
+				})
+			}
+		}
+	}
+
+	/// Gives the item what the eviction policy remembers about its key, if it was evicted not long ago.
+	///
+	/// Outside `EvictionScope::Bucket` the key may have been evicted from any of its candidate buckets
+	/// and may be put into another one, so all of them are checked.
+	fn recall_evicted_key(&mut self, item: &mut Item<K, V>) {
+		let target_bucket_index = self.get_target_bucket_index(&item.key);
+		let normal_buckets = std::iter::once(target_bucket_index)
+			.chain(self.get_probing_bucket_index(target_bucket_index))
+			.map(|index| (false, index));
+		let stash_buckets = (0..self.stash_size).map(|index| (true, index));
+		let candidate_buckets: Vec<_> = normal_buckets.chain(stash_buckets).collect();
+
+		for (is_stash, index) in candidate_buckets {
+			let bucket = self.get_bucket_mut(is_stash, index);
+			let remembers_evicted_keys = matches!(
+				bucket.get_eviction_policy(),
+				EvictionPolicy::TwoQ | EvictionPolicy::ClockPro
+			);
+			if remembers_evicted_keys && bucket.forget_evicted_key(&item.key) {
+				// A key evicted from A1in not long ago goes straight to Am,
+				// a non-resident cold key of CLOCK-Pro accessed during its test period comes back hot
+				item.policy_state.set_protected(true);
+			}
+			if let Some(history) = bucket.take_retained_history(&item.key) {
+				item.policy_state.set_history(history);
+			}
+			if bucket.is_in_lirs_stack(&item.key) {
+				// A non-resident HIR entry of S, the key had a short reuse distance and comes back as LIR
+				bucket
+					.get_policy_state_mut()
+					.get_lirs_mut()
+					.unwrap()
+					.remove_from_stack(&item.key);
+				item.policy_state.set_lir(true);
+			}
+		}
+	}
+
+	/// Puts the item into the segment, calling `evict` until there is room for it.
+	///
+	/// Every victim frees a slot in one of the candidate buckets, more than one victim is only needed
+	/// when the item is larger than the bytes it freed. Returns the evicted items, in eviction order.
+	fn put_evicting(
+		&mut self,
+		item: Item<K, V>,
+		mut evict: impl FnMut(&mut Self, &K) -> Option<Item<K, V>>,
+	) -> Vec<Item<K, V>> {
+		let mut item = item;
+		let mut evicted_items = Vec::new();
+		loop {
+			item = match self.try_put(item) {
+				Ok(()) => return evicted_items,
+				Err(rejected_item) => rejected_item,
+			};
+			match evict(self, &item.key) {
+				Some(victim) => evicted_items.push(victim),
+				None => break,
+			}
+		}
+		// Nothing is left to evict, the target bucket takes the item anyway
+		let target_bucket_index = self.get_target_bucket_index(&item.key);
+		evicted_items.extend(self.buckets[target_bucket_index].put(item).1);
+		evicted_items
+	}
+
+	/// S3-FIFO insert, a key remembered by the ghost queue goes to the main FIFO and any other key to the small FIFO.
+	///
+	/// Returns the evicted items.
+	fn put_s3_fifo(&mut self, item: Item<K, V>) -> Vec<Item<K, V>> {
+		if self.ghost.forget(hash(&item.key, &self.hash_builder)) {
+			return self.put_into_main(item);
+		}
+
+		let stash_bucket_index = self.get_stash_bucket_index(&item.key);
+		let mut evicted_items = Vec::new();
+		if self.stash_buckets[stash_bucket_index].is_full() {
+			// The oldest item of the small FIFO leaves it either way, freeing a slot
+			let oldest_item = self.remove_from_stash(stash_bucket_index, 0);
+			if oldest_item.lfu_counter > 0 {
+				let mut promoted_item = oldest_item;
+				promoted_item.lfu_counter = 0;
+				evicted_items = self.put_into_main(promoted_item);
+			} else {
+				self.ghost.remember(hash(&oldest_item.key, &self.hash_builder));
+				evicted_items.push(oldest_item);
+			}
+		}
+		let stash_evicted_items = self.put_into_stash(stash_bucket_index, item);
+		debug_assert!(stash_evicted_items.is_empty());
+		evicted_items
+	}
+
+	/// Puts the item into the less loaded of its target and probing buckets, the main FIFO of S3-FIFO.
+	///
+	/// If both are full, the oldest item of the chosen bucket is evicted unless it was accessed,
+	/// in which case it is reinserted with a decremented frequency and the next oldest one is considered.
+	fn put_into_main(&mut self, item: Item<K, V>) -> Vec<Item<K, V>> {
+		let target_bucket_index = self.get_target_bucket_index(&item.key);
+		let bucket_index = match self.get_probing_bucket_index(target_bucket_index) {
+			Some(probing_bucket_index)
+				if self.buckets[probing_bucket_index].size() < self.buckets[target_bucket_index].size() =>
+			{
+				probing_bucket_index
+			}
+			_ => target_bucket_index,
+		};
+
+		let bucket = &mut self.buckets[bucket_index];
+		let mut evicted_items = Vec::new();
+		while bucket.is_full() {
+			let mut oldest_item = bucket.remove_item(0);
+			if oldest_item.lfu_counter > 0 {
+				oldest_item.lfu_counter -= 1;
+				bucket.push_item(oldest_item);
+			} else {
+				evicted_items.push(oldest_item);
+			}
+		}
+		bucket.push_item(item);
+		evicted_items
+	}
+
+	/// ARC insert, the victim is taken from T1 or T2 across the candidate buckets according to the adaptive target.
+	///
+	/// A key remembered by a ghost list goes straight to T2.
+	/// Returns the evicted item, if any.
+	fn put_arc(&mut self, mut item: Item<K, V>) -> Vec<Item<K, V>> {
+		let key_hash = hash(&item.key, &self.hash_builder);
+		let (recency_len, frequency_len) = self.get_arc_list_lens();
+		let arc = self.arc.as_mut().unwrap();
+		let ghost_hit = arc.on_miss(key_hash, recency_len, frequency_len);
+		if ghost_hit != ArcGhostHit::None {
+			item.lfu_counter = 1;
+		}
+
+		let item = match self.try_put(item) {
+			Ok(()) => return Vec::new(),
+			Err(item) => item,
+		};
+		let Some((is_stash, bucket_index, position)) = self.select_arc_victim(&item.key, ghost_hit) else {
+			return Vec::new();
+		};
+		let evicted_item = self.evict_at(is_stash, bucket_index, position);
+		let evicted_hash = hash(&evicted_item.key, &self.hash_builder);
+		self
+			.arc
+			.as_mut()
+			.unwrap()
+			.remember(evicted_hash, evicted_item.lfu_counter == 0);
+
+		// The victim freed a slot in one of the candidate buckets
+		let rejected_item = self.try_put(item);
+		debug_assert!(rejected_item.is_ok());
+		vec![evicted_item]
+	}
+
+	/// Returns the least recently used item of the candidate buckets of `key` in the list the ARC `REPLACE` step picks,
+	/// or in the other list if none of the candidate buckets holds an item of the picked list.
+	fn select_arc_victim(&self, key: &K, ghost_hit: ArcGhostHit) -> Option<(bool, usize, usize)> {
+		let (recency_len, _) = self.get_arc_list_lens();
+		let from_recency = self.arc.as_ref()?.replace_from_recency(recency_len, ghost_hit);
+
+		self.select_victim_by(key, |item| ((item.lfu_counter == 0) != from_recency, item.timestamp))
+	}
+
+	/// Returns whether the item of the candidate buckets of `key` with the lowest `rank` lives in a stash bucket,
+	/// the index of its bucket and its position inside it.
+	fn select_victim_by<R: Ord>(&self, key: &K, rank: impl Fn(&Item<K, V>) -> R) -> Option<(bool, usize, usize)> {
+		let target_bucket_index = self.get_target_bucket_index(key);
+		let normal_buckets = std::iter::once(target_bucket_index)
+			.chain(self.get_probing_bucket_index(target_bucket_index))
+			.map(|index| (false, index));
+		let stash_buckets = (0..self.stash_size).map(|index| (true, index));
+		normal_buckets
+			.chain(stash_buckets)
+			.flat_map(|(is_stash, index)| {
+				let items = self.get_bucket(is_stash, index).get_items();
+				(0..items.len()).map(move |position| (is_stash, index, position, &items[position]))
+			})
+			.min_by_key(|&(_, _, _, item)| rank(item))
+			.map(|(is_stash, index, position, _)| (is_stash, index, position))
+	}
+
+	/// Returns the number of items of the segment in T1 and in T2.
+	fn get_arc_list_lens(&self) -> (usize, usize) {
+		let (recency_len, total_len) = self
+			.buckets
+			.iter()
+			.chain(&self.stash_buckets)
+			.flat_map(|bucket| bucket.get_items())
+			.fold((0, 0), |(recency_len, total_len), item| {
+				(recency_len + (item.lfu_counter == 0) as usize, total_len + 1)
+			});
+		(recency_len, total_len - recency_len)
+	}
+
+	/// Returns the number of bytes taken by the ghost histories of the segment and its buckets.
+	pub fn ghost_memory_usage(&self) -> usize {
+		let buckets_usage: usize = self
+			.buckets
+			.iter()
+			.chain(&self.stash_buckets)
+			.filter_map(|bucket| bucket.get_policy_state().get_ghost())
+			.map(GhostHistory::memory_usage)
+			.sum();
+		let arc_usage = self.arc.as_ref().map_or(0, |arc| arc.memory_usage());
+		self.ghost.memory_usage() + buckets_usage + arc_usage
+	}
+
+	/// Moves the least recently used protected items of the segment to the probationary region
+	/// until the protected region fits in its maximum size.
+	fn demote_protected_overflow(&mut self) {
+		let protected_items = || {
+			self
+				.buckets
+				.iter()
+				.chain(&self.stash_buckets)
+				.flat_map(|bucket| bucket.get_items())
+				.filter(|item| item.policy_state.is_protected())
+		};
+		let protected_size = protected_items().count();
+		if protected_size <= self.max_protected_size {
+			return;
+		}
+
+		let mut timestamps: Vec<_> = protected_items().map(|item| item.timestamp).collect();
+		timestamps.sort_unstable();
+		let threshold = timestamps[protected_size - self.max_protected_size - 1];
+		let now = self.clock.now();
+		for bucket in self.buckets.iter_mut().chain(self.stash_buckets.iter_mut()) {
+			for item in bucket
+				.get_items_mut()
+				.iter_mut()
+				.filter(|item| item.policy_state.is_protected() && item.timestamp <= threshold)
+			{
+				// The demoted items become the most recently used probationary items
+				item.policy_state.set_protected(false);
+				item.timestamp = now;
+			}
+		}
+	}
+
+	pub fn get_eviction_policy(&self) -> &EvictionPolicy {
+		self.buckets[0].get_eviction_policy()
+	}
+
+	fn is_s3_fifo(&self) -> bool {
+		// A table never evicts, S3-FIFO falls back to the stash first behaviour there
+		self.insert_mode == InsertMode::S3Fifo && self.mode == DashMode::Cache
+	}
+
+	/// Returns the item `put` would evict to make room for `key`, or `None` if there is room for it.
+	/// This function assumes that the key is not already in the segment.
+	///
+	/// S3-FIFO filters new keys through its small FIFO, so `None` is always returned for it.
+	pub fn peek_victim(&self, key: &K) -> Option<&Item<K, V>> {
+		let (is_stash, bucket_index, position) = match (self.eviction_scope, self.insert_mode) {
+			(_, InsertMode::S3Fifo) => return None,
+			_ if self.arc.is_some() => {
+				if self.has_room(key) {
+					return None;
+				}
+				let ghost_hit = self.arc.as_ref()?.find_ghost(hash(key, &self.hash_builder));
+				self.select_arc_victim(key, ghost_hit)?
+			}
+			(EvictionScope::Bucket, InsertMode::StashFirst) => {
+				let stash_bucket_index = self.get_stash_bucket_index(key);
+				let stash_bucket = &self.stash_buckets[stash_bucket_index];
+				if !stash_bucket.is_full() {
+					return None;
+				}
+				(true, stash_bucket_index, stash_bucket.get_victim_position()?)
+			}
+			(EvictionScope::Bucket, InsertMode::Balanced) => {
+				if self.has_room(key) {
+					return None;
+				}
+				let target_bucket_index = self.get_target_bucket_index(key);
+				(
+					false,
+					target_bucket_index,
+					self.buckets[target_bucket_index].get_victim_position()?,
+				)
+			}
+			// The sampled victim depends on the next sampling round, the candidate buckets' victim stands in for it
+			(EvictionScope::Segment | EvictionScope::SampledSegment | EvictionScope::SampledSharedPool, _) => {
+				if self.has_room(key) {
+					return None;
+				}
+				self.select_victim_from_candidates(key)?
+			}
+		};
+		Some(&self.get_bucket(is_stash, bucket_index).get_items()[position])
+	}
+
+	/// Returns whether `try_put` would find a free slot for `key`.
+	fn has_room(&self, key: &K) -> bool {
+		let target_bucket_index = self.get_target_bucket_index(key);
+		let probing_bucket_index = self.get_probing_bucket_index(target_bucket_index);
+		let normal_bucket_has_room = std::iter::once(target_bucket_index)
+			.chain(probing_bucket_index)
+			.any(|index| !self.buckets[index].is_full());
+		let can_displace_forward = probing_bucket_index
+			.and_then(|index| Some((index, self.get_probing_bucket_index(index)?)))
+			.is_some_and(|(index, next_bucket_index)| {
+				!self.buckets[next_bucket_index].is_full() && self.get_movable_item_position(index, index).is_some()
+			});
+		let can_displace_backward = target_bucket_index > 0
+			&& !self.buckets[target_bucket_index - 1].is_full()
+			&& self
+				.get_movable_item_position(target_bucket_index, target_bucket_index - 1)
+				.is_some();
+		let stash_has_room = self.stash_buckets.iter().any(|bucket| !bucket.is_full());
+
+		normal_bucket_has_room || can_displace_forward || can_displace_backward || stash_has_room
+	}
+
+	/// Evicts the item the eviction policy ranks first among the target, probing and stash buckets of `key`.
+	fn evict_from_candidates(&mut self, key: &K) -> Option<Item<K, V>> {
+		let (is_stash, bucket_index, position) = self.select_victim_from_candidates(key)?;
+		Some(self.evict_at(is_stash, bucket_index, position))
+	}
+
+	/// Evicts the item in `position` of a normal or stash bucket through `Bucket::evict_from_position`,
+	/// along with its overflow record.
+	fn evict_at(&mut self, is_stash: bool, bucket_index: usize, position: usize) -> Item<K, V> {
+		let evicted_item = self
+			.get_bucket_mut(is_stash, bucket_index)
+			.evict_from_position(position);
+		if is_stash {
+			self.remove_overflow_record(&evicted_item.key, bucket_index);
+		}
+		evicted_item
+	}
+
+	/// Returns whether the victim `evict_from_candidates` would pick lives in a stash bucket,
+	/// the index of its bucket and its position inside it.
+	fn select_victim_from_candidates(&self, key: &K) -> Option<(bool, usize, usize)> {
+		let target_bucket_index = self.get_target_bucket_index(key);
+		let normal_buckets = std::iter::once(target_bucket_index)
+			.chain(self.get_probing_bucket_index(target_bucket_index))
+			.map(|index| (false, index));
+		let stash_buckets = (0..self.stash_size).map(|index| (true, index));
+
+		let eviction_policy = self.buckets[target_bucket_index].get_eviction_policy();
+		if *eviction_policy == EvictionPolicy::TwoQ {
+			// A1in is emptied first once it grows over its share of the segment, or if Am has no candidate
+			let probationary_size = self
+				.buckets
+				.iter()
+				.chain(&self.stash_buckets)
+				.flat_map(|bucket| bucket.get_items())
+				.filter(|item| !item.policy_state.is_protected())
+				.count();
+			let evict_probationary = probationary_size > self.get_capacity().saturating_sub(self.max_protected_size);
+			return self.select_victim_by(key, |item| {
+				(item.policy_state.is_protected() == evict_probationary, item.timestamp)
+			});
+		}
+		let now = self.clock.now();
+		normal_buckets
+			.chain(stash_buckets)
+			.filter_map(|(is_stash, index)| Some((is_stash, index, self.get_bucket(is_stash, index).get_victim_position()?)))
+			.min_by(
+				|&(a_is_stash, a_index, a_position), &(b_is_stash, b_index, b_position)| {
+					eviction_policy.compare_victims(
+						&self.get_bucket(a_is_stash, a_index).get_items()[a_position],
+						&self.get_bucket(b_is_stash, b_index).get_items()[b_position],
+						now,
+					)
+				},
+			)
+	}
+
+	/// Evicts the victim the eviction pool of the segment picks for `key` after a sampling round.
+	///
+	/// Returns `None` without evicting anything if the segment has no eviction pool
+	/// or none of the candidates frees a slot `key` can use.
+	fn evict_sampled(&mut self, key: &K) -> Option<Item<K, V>> {
+		let mut eviction_pool = self.eviction_pool.take()?;
+		let now = self.clock.now();
+		let victim_key = eviction_pool.take_victim(
+			|rng| self.sample_key(rng),
+			|victim_key| self.locate(victim_key).is_some(),
+			|a, b| {
+				self
+					.get_eviction_policy()
+					.compare_victims(self.peek(a).unwrap(), self.peek(b).unwrap(), now)
+			},
+			|victim_key| self.can_evict_for(victim_key, key),
+		);
+		self.eviction_pool = Some(eviction_pool);
+		self.evict_for(&victim_key?, key)
+	}
+
+	/// Returns the key stored in a random slot of the segment, or `None` if the slot is empty.
+	pub fn sample_key(&self, rng: &mut StdRng) -> Option<K> {
+		let bucket_size = self.buckets[0].get_max_size();
+		let slot = rng.gen_range(0..self.get_capacity());
+		let bucket_index = slot / bucket_size;
+		let bucket = match bucket_index.checked_sub(self.segment_size) {
+			Some(stash_bucket_index) => &self.stash_buckets[stash_bucket_index],
+			None => &self.buckets[bucket_index],
+		};
+		bucket.get_items().get(slot % bucket_size).map(|item| item.key)
+	}
+
+	/// Returns a reference to the item with `key` without updating it, or `None` if the key is not found.
+	///
+	/// Unlike `get`, an expired item is returned.
+	pub fn peek(&self, key: &K) -> Option<&Item<K, V>> {
+		let (is_stash, bucket_index, position) = self.locate(key)?;
+		Some(&self.get_bucket(is_stash, bucket_index).get_items()[position])
+	}
+
+	/// Returns whether evicting the item with `victim_key` makes room for `key`.
+	///
+	/// That is the case if the victim is in the stash or in a candidate bucket of `key`,
+	/// or if a stashed item can move into the victim's bucket and free its stash slot.
+	pub fn can_evict_for(&self, victim_key: &K, key: &K) -> bool {
+		match self.locate(victim_key) {
+			Some((true, _, _)) => true,
+			Some((false, bucket_index, _)) => {
+				self.is_candidate_bucket(bucket_index, key) || self.get_unstashable_item(bucket_index).is_some()
+			}
+			None => false,
+		}
+	}
+
+	/// Evicts the item with `victim_key` and makes sure its slot can be used by `key`, see `can_evict_for`.
+	///
+	/// Returns the evicted item, or `None` if the victim is not in the segment.
+	pub fn evict_for(&mut self, victim_key: &K, key: &K) -> Option<Item<K, V>> {
+		let (is_stash, bucket_index, position) = self.locate(victim_key)?;
+		let evicted_item = self.evict_at(is_stash, bucket_index, position);
+		if is_stash {
+			return Some(evicted_item);
+		}
+		if !self.is_candidate_bucket(bucket_index, key) {
+			if let Some((stash_bucket_index, stash_position)) = self.get_unstashable_item(bucket_index) {
+				let item = self.remove_from_stash(stash_bucket_index, stash_position);
+				self.buckets[bucket_index].put(item);
+			}
+		}
+		Some(evicted_item)
+	}
+
+	/// Returns whether `bucket_index` is the target or probing bucket of `key`.
+	fn is_candidate_bucket(&self, bucket_index: usize, key: &K) -> bool {
+		let target_bucket_index = self.get_target_bucket_index(key);
+		bucket_index == target_bucket_index || Some(bucket_index) == self.get_probing_bucket_index(target_bucket_index)
+	}
+
+	/// Returns the stash bucket index and position of a stashed item that may be stored in bucket `bucket_index`.
+	fn get_unstashable_item(&self, bucket_index: usize) -> Option<(usize, usize)> {
+		self
+			.stash_buckets
+			.iter()
+			.enumerate()
+			.find_map(|(stash_bucket_index, stash_bucket)| {
+				let position = stash_bucket
+					.get_items()
+					.iter()
+					.position(|item| self.is_candidate_bucket(bucket_index, &item.key))?;
+				Some((stash_bucket_index, position))
+			})
+	}
+
+	fn get_bucket(&self, is_stash: bool, index: usize) -> &DashBucket<K, V, S> {
+		if is_stash {
+			&self.stash_buckets[index]
+		} else {
+			&self.buckets[index]
+		}
+	}
+
+	fn get_bucket_mut(&mut self, is_stash: bool, index: usize) -> &mut DashBucket<K, V, S> {
+		if is_stash {
+			&mut self.stash_buckets[index]
+		} else {
+			&mut self.buckets[index]
+		}
+	}
+
+	/// Insert the key, value pair into the segment without evicting anything.
+	///
+	/// Follows the Dash insert path: the less loaded of the target and probing buckets,
+	/// then displacing an item to its alternative bucket and finally a stash bucket.
+	/// In `InsertMode::StashFirst` and `InsertMode::S3Fifo` the stash bucket is tried first.
+	///
+	/// Returns the item back if none of these buckets has a free slot.
 	/// This function assumes that the key is not already in the segment.
-	pub fn put(&mut self, item: Item<K, V>) {
-		let stash_bucket = &mut self.stash_buckets[get_index(&item.key, self.stash_size)];
-		stash_bucket.put(item);
+	pub fn try_put(&mut self, item: Item<K, V>) -> Result<(), Item<K, V>> {
+		let stash_bucket_index = self.get_stash_bucket_index(&item.key);
+		let stash_first = matches!(self.insert_mode, InsertMode::StashFirst | InsertMode::S3Fifo);
+		if stash_first && self.stash_buckets[stash_bucket_index].has_room_for(&item) {
+			self.put_into_stash(stash_bucket_index, item);
+			return Ok(());
+		}
+
+		let target_bucket_index = self.get_target_bucket_index(&item.key);
+		let probing_bucket_index = self.get_probing_bucket_index(target_bucket_index);
+
+		// Balanced insert, ties go to the target bucket
+		let bucket_index = match probing_bucket_index {
+			Some(probing_bucket_index)
+				if self.buckets[probing_bucket_index].size() < self.buckets[target_bucket_index].size() =>
+			{
+				probing_bucket_index
+			}
+			_ => target_bucket_index,
+		};
+		if let Some(bucket_index) = [bucket_index, target_bucket_index]
+			.into_iter()
+			.chain(probing_bucket_index)
+			.find(|&index| self.buckets[index].has_room_for(&item))
+		{
+			self.buckets[bucket_index].put(item);
+			return Ok(());
+		}
+
+		// Displacement, both buckets are full
+		if let Some(probing_bucket_index) = probing_bucket_index {
+			if self.displace_forward(probing_bucket_index) {
+				self.buckets[probing_bucket_index].put(item);
+				return Ok(());
+			}
+		}
+		if self.displace_backward(target_bucket_index) {
+			self.buckets[target_bucket_index].put(item);
+			return Ok(());
+		}
+
+		// Stash, as a last resort. The overflow metadata records which stash bucket was used,
+		// so any stash bucket with a free slot will do
+		let free_stash_bucket_index = (0..self.stash_size)
+			.map(|offset| (stash_bucket_index + offset) % self.stash_size)
+			.find(|&index| self.stash_buckets[index].has_room_for(&item));
+		match free_stash_bucket_index {
+			Some(index) => {
+				self.put_into_stash(index, item);
+				Ok(())
+			}
+			None => Err(item),
+		}
+	}
+
+	/// Returns the stash bucket index and the position of `key` inside it.
+	///
+	/// Only the stash buckets recorded in the overflow metadata of the target and probing buckets are scanned,
+	/// unless the target bucket ran out of overflow slots.
+	fn find_in_stash(&self, key: &K) -> Option<(usize, usize)> {
+		let fingerprint = get_fingerprint(key, &self.hash_builder);
+		let target_bucket_index = self.get_target_bucket_index(key);
+		let probing_bucket_overflow = self
+			.get_probing_bucket_index(target_bucket_index)
+			.into_iter()
+			.flat_map(|index| self.buckets[index].get_overflow_stash_indexes(fingerprint, true));
+		let mut recorded = self.buckets[target_bucket_index]
+			.get_overflow_stash_indexes(fingerprint, false)
+			.chain(probing_bucket_overflow);
+		let found = recorded.find_map(|index| Some((index, self.stash_buckets[index].get_position(key)?)));
+		if found.is_some() || self.buckets[target_bucket_index].get_overflow_count() == 0 {
+			return found;
+		}
+
+		self
+			.stash_buckets
+			.iter()
+			.enumerate()
+			.find_map(|(index, stash_bucket)| Some((index, stash_bucket.get_position(key)?)))
+	}
+
+	/// Puts the item into stash bucket `stash_bucket_index` and records it in the overflow metadata.
+	///
+	/// Returns the items evicted from the stash bucket.
+	fn put_into_stash(&mut self, stash_bucket_index: usize, item: Item<K, V>) -> Vec<Item<K, V>> {
+		// With a capacity in bytes several items may have to leave, each one along with its overflow record
+		let mut evicted_items = Vec::new();
+		while self.stash_buckets[stash_bucket_index].get_max_bytes().is_some()
+			&& !self.stash_buckets[stash_bucket_index].has_room_for(&item)
+		{
+			let Some(position) = self.stash_buckets[stash_bucket_index].get_victim_position() else {
+				break;
+			};
+			evicted_items.push(self.evict_at(true, stash_bucket_index, position));
+		}
+
+		self.add_overflow_record(&item.key, stash_bucket_index);
+		let (_, victims) = self.stash_buckets[stash_bucket_index].put(item);
+		for victim in &victims {
+			self.remove_overflow_record(&victim.key, stash_bucket_index);
+		}
+		evicted_items.extend(victims);
+		evicted_items
+	}
+
+	/// Removes the item in `position` of stash bucket `stash_bucket_index` along with its overflow record.
+	fn remove_from_stash(&mut self, stash_bucket_index: usize, position: usize) -> Item<K, V> {
+		let item = self.stash_buckets[stash_bucket_index].remove_item(position);
+		self.remove_overflow_record(&item.key, stash_bucket_index);
+		item
+	}
+
+	/// Records a stashed item in its target bucket, or in its probing bucket if the target has no free
+	/// overflow slot. If neither has one, the overflow counter of the target bucket is incremented.
+	fn add_overflow_record(&mut self, key: &K, stash_bucket_index: usize) {
+		let fingerprint = get_fingerprint(key, &self.hash_builder);
+		let target_bucket_index = self.get_target_bucket_index(key);
+		if self.buckets[target_bucket_index].add_overflow(fingerprint, stash_bucket_index, false) {
+			return;
+		}
+		if let Some(probing_bucket_index) = self.get_probing_bucket_index(target_bucket_index) {
+			if self.buckets[probing_bucket_index].add_overflow(fingerprint, stash_bucket_index, true) {
+				return;
+			}
+		}
+		self.buckets[target_bucket_index].increment_overflow_count();
+	}
+
+	/// Reverts `add_overflow_record`.
+	fn remove_overflow_record(&mut self, key: &K, stash_bucket_index: usize) {
+		let fingerprint = get_fingerprint(key, &self.hash_builder);
+		let target_bucket_index = self.get_target_bucket_index(key);
+		if self.buckets[target_bucket_index].remove_overflow(fingerprint, stash_bucket_index, false) {
+			return;
+		}
+		if let Some(probing_bucket_index) = self.get_probing_bucket_index(target_bucket_index) {
+			if self.buckets[probing_bucket_index].remove_overflow(fingerprint, stash_bucket_index, true) {
+				return;
+			}
+		}
+		self.buckets[target_bucket_index].decrement_overflow_count();
+	}
+
+	/// Recomputes the overflow metadata from the items currently in the stash.
+	fn rebuild_overflow_metadata(&mut self) {
+		for bucket in self.buckets.iter_mut() {
+			bucket.clear_overflow();
+		}
+		for stash_bucket_index in 0..self.stash_size {
+			let keys: Vec<K> = self.stash_buckets[stash_bucket_index]
+				.get_items()
+				.iter()
+				.map(|item| item.key)
+				.collect();
+			for key in keys {
+				self.add_overflow_record(&key, stash_bucket_index);
+			}
+		}
+	}
+
+	/// Moves an item stored in its target bucket `bucket_index` to its probing bucket.
+	///
+	/// Returns whether a slot was freed in `bucket_index`.
+	fn displace_forward(&mut self, bucket_index: usize) -> bool {
+		let Some(next_bucket_index) = self.get_probing_bucket_index(bucket_index) else {
+			return false;
+		};
+		if self.buckets[next_bucket_index].is_full() {
+			return false;
+		}
+		self.move_item(bucket_index, next_bucket_index, bucket_index)
+	}
+
+	/// Moves an item stored in its probing bucket `bucket_index` back to its target bucket.
+	///
+	/// Returns whether a slot was freed in `bucket_index`.
+	fn displace_backward(&mut self, bucket_index: usize) -> bool {
+		if bucket_index == 0 || self.buckets[bucket_index - 1].is_full() {
+			return false;
+		}
+		self.move_item(bucket_index, bucket_index - 1, bucket_index - 1)
+	}
+
+	/// Moves the first item of bucket `from` whose target bucket is `target` into bucket `to`.
+	fn move_item(&mut self, from: usize, to: usize, target: usize) -> bool {
+		let position = self
+			.get_movable_item_position(from, target)
+			.filter(|&position| self.buckets[to].has_room_for(&self.buckets[from].get_items()[position]));
+		match position {
+			Some(position) => {
+				let item = self.buckets[from].remove_item(position);
+				self.buckets[to].put(item);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Returns the position of the first item of bucket `from` whose target bucket is `target`.
+	fn get_movable_item_position(&self, from: usize, target: usize) -> Option<usize> {
+		self.buckets[from]
+			.get_items()
+			.iter()
+			.position(|item| self.get_target_bucket_index(&item.key) == target)
+	}
+
+	fn get_target_bucket_index(&self, key: &K) -> usize {
+		self.layout.bucket_index(hash(key, &self.hash_builder))
+	}
+
+	fn get_stash_bucket_index(&self, key: &K) -> usize {
+		self.layout.stash_bucket_index(hash(key, &self.hash_builder))
+	}
+
+	/// Returns the index of the bucket next to `target_bucket_index`, if there is one.
+	fn get_probing_bucket_index(&self, target_bucket_index: usize) -> Option<usize> {
+		let probing_bucket_index = target_bucket_index + 1;
+		(probing_bucket_index < self.segment_size).then_some(probing_bucket_index)
+	}
+
+	/// Splits the segment in two according to the next most significant hash bit.
+	///
+	/// Items whose bit is set move to the returned segment, the rest stay here.
+	/// Each item keeps the bucket index it had, so both halves are guaranteed to fit.
+	pub fn split(&mut self) -> DashSegment<K, V, S> {
+		let split_depth = self.local_depth;
+		self.local_depth += 1;
+
+		let mut sibling = DashSegment {
+			buckets: self.empty_like(&self.buckets),
+			segment_size: self.segment_size,
+			stash_buckets: self.empty_like(&self.stash_buckets),
+			stash_size: self.stash_size,
+			local_depth: self.local_depth,
+			layout: self.layout,
+			mode: self.mode,
+			insert_mode: self.insert_mode,
+			eviction_scope: self.eviction_scope,
+			ghost: GhostHistory::new(self.ghost.capacity()),
+			max_protected_size: self.max_protected_size,
+			arc: self
+				.arc
+				.as_ref()
+				.map(|arc| ArcState::new(self.get_capacity(), arc.get_ghost_capacity())),
+			eviction_pool: self.eviction_pool.as_mut().map(|eviction_pool| eviction_pool.fork()),
+			clock: self.clock.clone(),
+			hash_builder: self.hash_builder.clone(),
+		};
+
+		let pairs = self
+			.buckets
+			.iter_mut()
+			.zip(sibling.buckets.iter_mut())
+			.chain(self.stash_buckets.iter_mut().zip(sibling.stash_buckets.iter_mut()));
+		for (bucket, sibling_bucket) in pairs {
+			for item in bucket.take_items() {
+				// Pushing directly keeps the relative order the eviction policy relies on
+				if HashLayout::directory_bit(hash(&item.key, &self.hash_builder), split_depth) {
+					sibling_bucket.push_item(item);
+				} else {
+					bucket.push_item(item);
+				}
+			}
+		}
+		self.rebuild_overflow_metadata();
+		sibling.rebuild_overflow_metadata();
+		sibling
+	}
+
+	/// Returns the number of items the segment can hold.
+	fn get_capacity(&self) -> usize {
+		self
+			.buckets
+			.iter()
+			.chain(&self.stash_buckets)
+			.map(|bucket| bucket.get_max_size())
+			.sum()
+	}
+
+	fn empty_like(&self, buckets: &[DashBucket<K, V, S>]) -> Vec<DashBucket<K, V, S>> {
+		buckets
+			.iter()
+			.map(|bucket| {
+				DashBucket::new(
+					bucket.get_max_size(),
+					bucket.get_max_protected_size(),
+					bucket.get_eviction_policy().clone(),
+					self.hash_builder.clone(),
+				)
+				.with_max_bytes(bucket.get_max_bytes())
+				.with_ghost_capacity(bucket.get_policy_state().get_ghost().map(GhostHistory::capacity))
+				.with_clock(self.clock.clone())
+			})
+			.collect()
 	}
 }
 
-impl<K, V> Display for DashSegment<K, V>
+impl<K, V, S> Display for DashSegment<K, V, S>
 where
 	K: Hash + Eq + Copy + Debug + Display,
 	V: Eq + Copy + Debug + Display,
+	S: BuildHasher + Clone + Debug,
 {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		writeln!(f, "Segment {{")?;
@@ -125,9 +1221,414 @@ where
 	}
 }
 
-// TODO: implement
-/*
 #[cfg(test)]
 mod tests {
+	use super::*;
+	use crate::hasher::DefaultBuildHasher;
+
+	type TestSegment = DashSegment<i64, i64, DefaultBuildHasher>;
+
+	/// Returns the keys of the evicted items, in eviction order.
+	fn evicted_keys(items: Vec<Item<i64, i64>>) -> Vec<i64> {
+		items.into_iter().map(|item| item.key).collect()
+	}
+
+	fn new_segment(settings: DashSettings) -> TestSegment {
+		DashSegment::new(settings, 0, DefaultBuildHasher::default())
+	}
+
+	fn settings(insert_mode: InsertMode) -> DashSettings {
+		DashSettings {
+			num_of_normal_buckets: 4,
+			num_of_stash_buckets: 2,
+			bucket_size: 2,
+			insert_mode,
+			..DashSettings::default()
+		}
+	}
+
+	/// Returns `count` keys whose target bucket is `target_bucket_index`.
+	fn keys_with_target(target_bucket_index: usize, segment_size: usize, count: usize) -> Vec<i64> {
+		(0..)
+			.filter(|key| {
+				let hash = hash(key, &DefaultBuildHasher::default());
+				HashLayout::new(1, segment_size, 1).bucket_index(hash) == target_bucket_index
+			})
+			.take(count)
+			.collect()
+	}
+
+	fn stash_len(segment: &TestSegment) -> usize {
+		segment.stash_buckets.iter().map(|bucket| bucket.size()).sum()
+	}
+
+	#[test]
+	fn test_stash_first_puts_into_stash() {
+		let mut segment = new_segment(settings(InsertMode::StashFirst));
+		segment.put(Item::new(1, 1));
+		assert_eq!(stash_len(&segment), 1);
+
+		// A hit promotes the item to its target bucket
+		assert_eq!(segment.get(&1).map(|item| item.value), Some(1));
+		assert_eq!(stash_len(&segment), 0);
+	}
+
+	#[test]
+	fn test_stash_promotion_never_evicts() {
+		let mut segment = new_segment(DashSettings {
+			num_of_stash_buckets: 1,
+			bucket_capacity_bytes: Some(4),
+			..settings(InsertMode::StashFirst)
+		});
+		let keys = keys_with_target(1, 4, 4);
+		for &key in &keys[..2] {
+			segment.put(Item::new(key, key).with_size(2));
+			segment.get(&key);
+		}
+		assert!(segment.buckets[1].is_full());
+
+		// Swapping with the victim of the full target bucket would overflow its bytes
+		segment.put(Item::new(keys[2], keys[2]).with_size(3));
+		assert_eq!(segment.get(&keys[2]).map(|item| item.value), Some(keys[2]));
+		assert_eq!(stash_len(&segment), 1);
+		assert!(keys[..3].iter().all(|key| segment.peek(key).is_some()));
+
+		// The victim of the target bucket takes the stash slot of the promoted item
+		segment.remove(&keys[2]);
+		segment.put(Item::new(keys[3], keys[3]).with_size(1));
+		assert_eq!(segment.get(&keys[3]).map(|item| item.value), Some(keys[3]));
+		assert!(segment.buckets[1].get_position(&keys[3]).is_some());
+		assert!(segment.find_in_stash(&keys[0]).is_some());
+		assert_eq!(overflow_records(&segment), stash_len(&segment));
+	}
+
+	#[test]
+	fn test_balanced_insert_fills_target_and_probing_before_stash() {
+		let mut segment = new_segment(settings(InsertMode::Balanced));
+		let keys = keys_with_target(1, 4, 5);
+		for &key in &keys[..4] {
+			segment.put(Item::new(key, key));
+		}
+		assert_eq!(segment.buckets[1].size(), 2);
+		assert_eq!(segment.buckets[2].size(), 2);
+		assert_eq!(stash_len(&segment), 0);
+
+		segment.put(Item::new(keys[4], keys[4]));
+		assert_eq!(stash_len(&segment), 1);
+		for key in keys {
+			assert_eq!(segment.get(&key).map(|item| item.value), Some(key));
+		}
+	}
+
+	#[test]
+	fn test_balanced_insert_displaces_to_alternative_bucket() {
+		let mut segment = new_segment(settings(InsertMode::Balanced));
+		let keys_of_1 = keys_with_target(1, 4, 3);
+		let keys_of_2 = keys_with_target(2, 4, 3);
+		for &key in keys_of_2.iter().chain(&keys_of_1[..2]) {
+			segment.put(Item::new(key, key));
+		}
+		assert!(segment.buckets[1].is_full() && segment.buckets[2].is_full());
+		assert_eq!(segment.buckets[3].size(), 1);
+
+		// Bucket 2 holds items that belong there, one of them moves on to bucket 3 to make room
+		segment.put(Item::new(keys_of_1[2], keys_of_1[2]));
+		assert_eq!(segment.buckets[3].size(), 2);
+		assert_eq!(stash_len(&segment), 0);
+		for key in keys_of_1.into_iter().chain(keys_of_2) {
+			assert_eq!(segment.get(&key).map(|item| item.value), Some(key));
+		}
+	}
+
+	/// Returns the number of stashed items accounted for by the overflow metadata.
+	fn overflow_records(segment: &TestSegment) -> usize {
+		segment
+			.buckets
+			.iter()
+			.map(|bucket| {
+				let recorded: usize = (0..=u8::MAX)
+					.map(|fingerprint| bucket.get_overflow_stash_indexes(fingerprint, false).count())
+					.sum();
+				let recorded_for_previous: usize = (0..=u8::MAX)
+					.map(|fingerprint| bucket.get_overflow_stash_indexes(fingerprint, true).count())
+					.sum();
+				recorded + recorded_for_previous + bucket.get_overflow_count()
+			})
+			.sum()
+	}
+
+	#[test]
+	fn test_overflow_metadata_tracks_stash() {
+		for insert_mode in [InsertMode::StashFirst, InsertMode::Balanced, InsertMode::S3Fifo] {
+			let mut segment = new_segment(settings(insert_mode));
+			for key in 0..200 {
+				segment.put(Item::new(key, key));
+				segment.get(&(key / 3));
+				assert_eq!(overflow_records(&segment), stash_len(&segment));
+			}
+
+			for stash_bucket in &segment.stash_buckets {
+				for item in stash_bucket.get_items() {
+					assert!(segment.find_in_stash(&item.key).is_some());
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_miss_without_overflow_record_skips_stash() {
+		let mut segment = new_segment(settings(InsertMode::StashFirst));
+		segment.put(Item::new(1, 1));
+
+		// Sneak an item into the stash behind the metadata's back, a lookup must not see it
+		let stash_bucket_index = segment.get_stash_bucket_index(&2);
+		segment.stash_buckets[stash_bucket_index].put(Item::new(2, 2));
+		assert!(segment.find_in_stash(&1).is_some());
+		assert!(segment.find_in_stash(&2).is_none());
+	}
+
+	#[test]
+	fn test_segment_scope_evicts_across_candidate_buckets() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 2,
+			num_of_stash_buckets: 1,
+			bucket_size: 1,
+			eviction_policy: EvictionPolicy::Lfu,
+			eviction_scope: EvictionScope::Segment,
+			..DashSettings::default()
+		});
+		let keys = keys_with_target(0, 2, 4);
+		for &key in &keys[..3] {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		// The stashed item is the only one never accessed
+		segment.get(&keys[0]);
+		segment.get(&keys[1]);
+		assert_eq!(segment.stash_buckets[0].get_items()[0].key, keys[2]);
+
+		let evicted_items = segment.put(Item::new(keys[3], keys[3]));
+		assert_eq!(evicted_keys(evicted_items), vec![keys[2]]);
+		for &key in [keys[0], keys[1], keys[3]].iter() {
+			assert_eq!(segment.get(&key).map(|item| item.value), Some(key));
+		}
+	}
+
+	#[test]
+	fn test_segment_scope_sweeps_sieve_hand_to_victim() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			eviction_policy: EvictionPolicy::Sieve,
+			eviction_scope: EvictionScope::Segment,
+			..DashSettings::default()
+		});
+		for key in 0..4 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		segment.get(&0);
+
+		// The hand passes over the visited item on its way to the victim
+		assert_eq!(evicted_keys(segment.put(Item::new(4, 4))), vec![1]);
+		assert!(!segment.peek(&0).unwrap().policy_state.is_visited());
+		assert_eq!(segment.buckets[0].get_policy_state().get_hand(), 1);
+	}
+
+	#[test]
+	fn test_segment_scope_clock_pro_remembers_evicted_keys() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			eviction_policy: EvictionPolicy::ClockPro,
+			eviction_scope: EvictionScope::Segment,
+			..DashSettings::default()
+		});
+		for key in 0..4 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		assert_eq!(evicted_keys(segment.put(Item::new(4, 4))), vec![0]);
+
+		// 0 comes back during its test period, so it is hot
+		assert_eq!(evicted_keys(segment.put(Item::new(0, 0))), vec![1]);
+		assert!(segment.peek(&0).unwrap().policy_state.is_protected());
+	}
+
+	#[test]
+	fn test_segment_scope_lirs_keeps_non_resident_entries() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			eviction_policy: EvictionPolicy::Lirs,
+			eviction_scope: EvictionScope::Segment,
+			..DashSettings::default()
+		});
+		for key in 0..4 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		assert_eq!(evicted_keys(segment.put(Item::new(4, 4))), vec![1]);
+		assert!(segment.buckets[0].is_in_lirs_stack(&1));
+
+		// 1 comes back to the stash, its entry in S of the bucket it left makes it LIR
+		assert_eq!(evicted_keys(segment.put(Item::new(1, 1))), vec![3]);
+		assert!(segment.find_in_stash(&1).is_some());
+		assert!(segment.peek(&1).unwrap().policy_state.is_lir());
+		assert!(!segment.buckets[0].is_in_lirs_stack(&1));
+	}
+
+	#[test]
+	fn test_sampled_segment_evicts_outside_candidate_buckets() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 2,
+			num_of_stash_buckets: 1,
+			bucket_size: 1,
+			eviction_policy: EvictionPolicy::Lfu,
+			eviction_scope: EvictionScope::SampledSegment,
+			eviction_samples: 16,
+			..DashSettings::default()
+		});
+		let keys_of_0 = keys_with_target(0, 2, 2);
+		let keys_of_1 = keys_with_target(1, 2, 2);
+		for key in [keys_of_0[0], keys_of_1[0], keys_of_0[1]] {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		segment.get(&keys_of_1[0]);
+		segment.get(&keys_of_0[1]);
+
+		// The least frequently used item is in bucket 0, the stashed item moves there to make room in the stash
+		let evicted_items = segment.put(Item::new(keys_of_1[1], keys_of_1[1]));
+		assert_eq!(evicted_keys(evicted_items), vec![keys_of_0[0]]);
+		assert_eq!(segment.buckets[0].get_items()[0].key, keys_of_0[1]);
+		assert_eq!(overflow_records(&segment), 1);
+		for key in [keys_of_1[0], keys_of_0[1], keys_of_1[1]] {
+			assert_eq!(segment.get(&key).map(|item| item.value), Some(key));
+		}
+	}
+
+	#[test]
+	fn test_s3_fifo_promotes_accessed_items_and_remembers_evicted_ones() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			insert_mode: InsertMode::S3Fifo,
+			..DashSettings::default()
+		});
+		for key in 0..2 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		segment.get(&1);
+
+		// 0 was never accessed, it leaves the small FIFO for the ghost queue
+		assert_eq!(evicted_keys(segment.put(Item::new(2, 2))), vec![0]);
+		// 1 was accessed, it moves to the main FIFO instead of being evicted
+		assert!(segment.put(Item::new(3, 3)).is_empty());
+		assert_eq!(segment.buckets[0].get_position(&1), Some(0));
+
+		// 0 comes back straight into the main FIFO
+		assert!(segment.put(Item::new(0, 0)).is_empty());
+		assert_eq!(segment.buckets[0].get_position(&0), Some(1));
+		assert_eq!(stash_len(&segment), 2);
+	}
+
+	#[test]
+	fn test_s3_fifo_update_counts_as_access() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			insert_mode: InsertMode::S3Fifo,
+			..DashSettings::default()
+		});
+		for key in 0..2 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		assert_eq!(segment.update(Item::new(0, 10)), Ok(0));
+
+		// 0 was updated, it moves to the main FIFO instead of being evicted
+		assert!(segment.put(Item::new(2, 2)).is_empty());
+		assert_eq!(segment.buckets[0].get_position(&0), Some(0));
+		assert_eq!(segment.get(&0).map(|item| item.value), Some(10));
+	}
+
+	#[test]
+	fn test_ghost_capacity_bounds_remembered_keys() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			insert_mode: InsertMode::S3Fifo,
+			ghost_capacity: Some(1),
+			..DashSettings::default()
+		});
+		for key in 0..4 {
+			segment.put(Item::new(key, key));
+		}
+		// 0 and 1 were evicted from the small FIFO, only 1 is still remembered
+		assert_eq!(segment.ghost_memory_usage(), std::mem::size_of::<u64>());
+		assert!(segment.put(Item::new(1, 1)).is_empty());
+		assert_eq!(segment.buckets[0].get_position(&1), Some(0));
+		assert_eq!(evicted_keys(segment.put(Item::new(0, 0))), vec![2]);
+		assert_eq!(segment.buckets[0].get_position(&0), None);
+	}
+
+	#[test]
+	fn test_arc_ghost_hit_grows_recency_target() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 1,
+			num_of_stash_buckets: 1,
+			bucket_size: 1,
+			eviction_policy: EvictionPolicy::Arc,
+			..DashSettings::default()
+		});
+		for key in 0..2 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		segment.get(&1);
+
+		// T1 is over its target, its least recently used item moves to B1
+		assert_eq!(evicted_keys(segment.put(Item::new(2, 2))), vec![0]);
+
+		// The B1 hit makes room for T1, so the victim now comes from T2
+		assert_eq!(evicted_keys(segment.put(Item::new(0, 0))), vec![1]);
+		assert_eq!(segment.get(&2).map(|item| item.value), Some(2));
+		assert_eq!(segment.get(&0).map(|item| item.lfu_counter), Some(2));
+	}
+
+	#[test]
+	fn test_segment_slru_limits_protected_region_across_buckets() {
+		let mut segment = new_segment(DashSettings {
+			num_of_normal_buckets: 2,
+			num_of_stash_buckets: 1,
+			bucket_size: 2,
+			eviction_policy: EvictionPolicy::Slru,
+			eviction_scope: EvictionScope::Segment,
+			protected_fraction: 1.0 / 6.0,
+			..DashSettings::default()
+		});
+		for key in 0..6 {
+			assert!(segment.put(Item::new(key, key)).is_empty());
+		}
+		let protected = |segment: &TestSegment| -> Vec<i64> {
+			segment
+				.buckets
+				.iter()
+				.chain(&segment.stash_buckets)
+				.flat_map(|bucket| bucket.get_items())
+				.filter(|item| item.policy_state.is_protected())
+				.map(|item| item.key)
+				.collect()
+		};
+		for key in 0..6 {
+			segment.get(&key);
+		}
+		assert_eq!(protected(&segment), vec![5]);
+
+		// Updates promote and demote the same way
+		for key in 0..6 {
+			assert_eq!(segment.update(Item::new(key, key * 10)), Ok(key));
+		}
+		assert_eq!(protected(&segment), vec![5]);
+	}
 }
-*/
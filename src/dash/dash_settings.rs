@@ -1,4 +1,93 @@
 use crate::eviction_policy::EvictionPolicy;
+use std::time::Duration;
+
+/// Selects how Dash behaves once a segment runs out of room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DashMode {
+	/** Fixed number of segments, a full bucket evicts according to the eviction policy */
+	Cache,
+	/** Extendible hashing (Dash-EH), a full segment splits and the directory doubles when needed */
+	Table,
+}
+
+impl DashMode {
+	pub fn from_usize(index: usize) -> Option<Self> {
+		match index {
+			0 => Some(DashMode::Cache),
+			1 => Some(DashMode::Table),
+			_ => None,
+		}
+	}
+}
+
+/// Selects where `DashSegment::put` places new items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertMode {
+	/** New items go to a stash bucket and are promoted to their target bucket on a later hit */
+	StashFirst,
+	/** Dash insert: less loaded of target and probing buckets, then displacement, then the stash */
+	Balanced,
+	/** S3-FIFO: the stash is the small FIFO, the normal buckets the main FIFO and evicted small FIFO keys are remembered by a ghost queue. Buckets are kept in FIFO order, the eviction policy is ignored */
+	S3Fifo,
+}
+
+impl InsertMode {
+	pub fn from_usize(index: usize) -> Option<Self> {
+		match index {
+			0 => Some(InsertMode::StashFirst),
+			1 => Some(InsertMode::Balanced),
+			2 => Some(InsertMode::S3Fifo),
+			_ => None,
+		}
+	}
+}
+
+/// Selects where the victim is taken from once there is no room for a new item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionScope {
+	/** The bucket the item is put into evicts according to the eviction policy */
+	Bucket,
+	/** The victim is selected across the target, probing and stash buckets according to the eviction policy */
+	Segment,
+	/** Redis-style approximation: random items of the whole segment are sampled into an eviction pool and the worst one is evicted */
+	SampledSegment,
+	/** Like `SampledSegment` with a single eviction pool shared by every segment, only used in `DashMode::Cache`; each round samples the segment of the new key */
+	SampledSharedPool,
+}
+
+impl EvictionScope {
+	pub fn from_usize(index: usize) -> Option<Self> {
+		match index {
+			0 => Some(EvictionScope::Bucket),
+			1 => Some(EvictionScope::Segment),
+			2 => Some(EvictionScope::SampledSegment),
+			3 => Some(EvictionScope::SampledSharedPool),
+			_ => None,
+		}
+	}
+}
+
+/// Selects whether a new key has to earn its place in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmissionPolicy {
+	/** Every new key is admitted */
+	AdmitAll,
+	/** Plain TinyLFU, without a window: a new key is admitted only if it was accessed more often than the item it would evict */
+	TinyLfu,
+	/** W-TinyLFU: new keys go through an LRU window on top of the buckets, TinyLFU filters the items it pushes out */
+	WTinyLfu,
+}
+
+impl AdmissionPolicy {
+	pub fn from_usize(index: usize) -> Option<Self> {
+		match index {
+			0 => Some(AdmissionPolicy::AdmitAll),
+			1 => Some(AdmissionPolicy::TinyLfu),
+			2 => Some(AdmissionPolicy::WTinyLfu),
+			_ => None,
+		}
+	}
+}
 
 #[derive(Debug, Clone)]
 pub struct DashSettings {
@@ -7,6 +96,29 @@ pub struct DashSettings {
 	pub num_of_stash_buckets: usize,
 	pub bucket_size: usize,
 	pub eviction_policy: EvictionPolicy,
+	pub mode: DashMode,
+	pub insert_mode: InsertMode,
+	pub eviction_scope: EvictionScope,
+	pub admission_policy: AdmissionPolicy,
+	/// Share of a bucket, or of a segment with `EvictionScope::Segment`, the protected region of SLRU and 2Q may take.
+	pub protected_fraction: f64,
+	/// Number of items sampled every eviction round with the sampled eviction scopes.
+	pub eviction_samples: usize,
+	/// Number of eviction candidates kept between rounds with the sampled eviction scopes.
+	pub eviction_pool_size: usize,
+	/// Seed of the RNG the sampled eviction scopes sample with.
+	pub sampling_seed: u64,
+	/// Capacity of a bucket in bytes, on top of `bucket_size` items. `None` to only limit the number of items.
+	/// S3-FIFO and ARC count items only and do not support it.
+	pub bucket_capacity_bytes: Option<usize>,
+	/// Number of evicted keys a ghost history remembers: per bucket for 2Q and CLOCK-Pro, per segment for S3-FIFO,
+	/// and per ghost list for ARC.
+	/// `None` for the default of each policy.
+	pub ghost_capacity: Option<usize>,
+	/// Time to live of the items put without one, `None` if they never expire.
+	pub default_ttl: Option<Duration>,
+	/// Number of buckets checked for expired items on every put, 0 to only reclaim expired items when there is no room.
+	pub expiration_sweep_buckets: usize,
 	pub debug_mode: usize,
 }
 
@@ -18,6 +130,18 @@ impl Default for DashSettings {
 			num_of_stash_buckets: 4,
 			bucket_size: 16,
 			eviction_policy: EvictionPolicy::ClassicLRU,
+			mode: DashMode::Cache,
+			insert_mode: InsertMode::Balanced,
+			eviction_scope: EvictionScope::Bucket,
+			admission_policy: AdmissionPolicy::AdmitAll,
+			protected_fraction: 0.8,
+			eviction_samples: 5,
+			eviction_pool_size: 16,
+			sampling_seed: 0,
+			bucket_capacity_bytes: None,
+			ghost_capacity: None,
+			default_ttl: None,
+			expiration_sweep_buckets: 0,
 			debug_mode: 1,
 		}
 	}
@@ -30,5 +154,17 @@ pub const DRAGON_SETTINGS: DashSettings = DashSettings {
 	num_of_stash_buckets: 4,
 	bucket_size: 14,
 	eviction_policy: EvictionPolicy::ClassicLRU,
+	mode: DashMode::Cache,
+	insert_mode: InsertMode::Balanced,
+	eviction_scope: EvictionScope::Bucket,
+	admission_policy: AdmissionPolicy::AdmitAll,
+	protected_fraction: 0.8,
+	eviction_samples: 5,
+	eviction_pool_size: 16,
+	sampling_seed: 0,
+	bucket_capacity_bytes: None,
+	ghost_capacity: None,
+	default_ttl: None,
+	expiration_sweep_buckets: 0,
 	debug_mode: 1,
 };
@@ -0,0 +1,53 @@
+use std::fmt::{Display, Formatter};
+
+/// Describes how evenly the items are spread over the normal buckets.
+///
+/// A well distributed hash gives a small coefficient of variation and no empty buckets once the table is loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketLoadSkew {
+	pub num_of_buckets: usize,
+	pub empty_buckets: usize,
+	pub min_load: usize,
+	pub max_load: usize,
+	pub mean_load: f64,
+	pub std_dev: f64,
+	/// Standard deviation divided by the mean, 0 when the table is empty.
+	pub coefficient_of_variation: f64,
+}
+
+impl BucketLoadSkew {
+	/// Computes the skew from the number of items in every bucket.
+	pub fn from_loads(loads: &[usize]) -> Self {
+		let num_of_buckets = loads.len();
+		let mean_load = loads.iter().sum::<usize>() as f64 / num_of_buckets.max(1) as f64;
+		let variance =
+			loads.iter().map(|&load| (load as f64 - mean_load).powi(2)).sum::<f64>() / num_of_buckets.max(1) as f64;
+		let std_dev = variance.sqrt();
+
+		BucketLoadSkew {
+			num_of_buckets,
+			empty_buckets: loads.iter().filter(|&&load| load == 0).count(),
+			min_load: loads.iter().copied().min().unwrap_or(0),
+			max_load: loads.iter().copied().max().unwrap_or(0),
+			mean_load,
+			std_dev,
+			coefficient_of_variation: if mean_load > 0.0 { std_dev / mean_load } else { 0.0 },
+		}
+	}
+}
+
+impl Display for BucketLoadSkew {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Bucket Load {{ buckets: {}, empty: {}, min: {}, max: {}, mean: {:.2}, std_dev: {:.2}, cv: {:.3} }}",
+			self.num_of_buckets,
+			self.empty_buckets,
+			self.min_load,
+			self.max_load,
+			self.mean_load,
+			self.std_dev,
+			self.coefficient_of_variation
+		)
+	}
+}
@@ -1,15 +1,48 @@
-#[derive(Debug, Clone)]
+use crate::shared::item::Item;
+use std::{cmp::Ordering, hash::Hash, time::Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EvictionPolicy {
 	/** Every cache hit the item will move to the beginning of the data structure */
 	ClassicLRU,
 	/** LRU will be checked based of timestamp of the last hit and items won't move */
 	TimestampLRU,
 	Lifo,
+	/** Evicts the least frequently used item, ties are broken by the last access */
 	Lfu,
 	Fifo,
+	/** FIFO order with a visited bit per item, a hand sweeps the bucket and evicts the first unvisited item */
+	Sieve,
+	/** Adaptive Replacement Cache: items are in T1 until their first hit and in T2 afterwards, Dash adapts the target size of T1 per segment (sampled scopes are rejected), `AssociativeCache` has no ghost lists nor target and always evicts from T1 first */
+	Arc,
+	/** 2Q: new items enter a probationary FIFO (A1in), keys evicted from it are remembered (A1out) and enter the protected LRU (Am) if they come back */
+	TwoQ,
+	/** Segmented LRU: new items enter a probationary LRU and move to a size-limited protected LRU on a hit, its overflow is demoted back */
+	Slru,
+	/** LIRS: items are LIR or HIR according to their inter-reference recency, only resident HIR items are evicted */
+	Lirs,
+	/** Second chance: a hit sets the reference bit of the item, a hand clears the bits it passes over and replaces the first item without one in place */
+	Clock,
+	/** CLOCK-Pro: items are hot or cold, referenced cold items become hot and evicted cold keys are remembered for a test period */
+	ClockPro,
+	/** LFU whose counters are halved after a number of accesses proportional to the bucket size, so old popularity fades */
+	LfuAging,
+	/** LFU with dynamic aging: the priority of an item is its frequency plus the cache age, which is the priority of the last victim */
+	Lfuda,
+	/** LRU-K: evicts the item whose K-th most recent access is the oldest, items accessed fewer than K times go first */
+	LruK {
+		k: usize,
+	},
+	/** Hyperbolic caching: evicts the item with the fewest accesses per unit of time since it was put into the cache */
+	Hyperbolic,
+	/** Greedy-Dual-Size-Frequency: the priority of an item is its frequency divided by its size plus the cache age, like LFUDA */
+	Gdsf,
 }
 
 impl EvictionPolicy {
+	/// Returns the policy with the given id, as passed by the JNI init functions.
+	///
+	/// The id 14 is LRU-2, the usual choice of K, use `lru_k` for other values.
 	pub fn from_usize(index: usize) -> Option<Self> {
 		match index {
 			0 => Some(EvictionPolicy::ClassicLRU),
@@ -17,7 +50,85 @@ impl EvictionPolicy {
 			2 => Some(EvictionPolicy::Lfu),
 			3 => Some(EvictionPolicy::Fifo),
 			4 => Some(EvictionPolicy::TimestampLRU),
+			5 => Some(EvictionPolicy::Sieve),
+			6 => Some(EvictionPolicy::Arc),
+			7 => Some(EvictionPolicy::TwoQ),
+			8 => Some(EvictionPolicy::Slru),
+			9 => Some(EvictionPolicy::Lirs),
+			10 => Some(EvictionPolicy::Clock),
+			11 => Some(EvictionPolicy::ClockPro),
+			12 => Some(EvictionPolicy::LfuAging),
+			13 => Some(EvictionPolicy::Lfuda),
+			14 => Some(EvictionPolicy::lru_k(2)),
+			15 => Some(EvictionPolicy::Hyperbolic),
+			16 => Some(EvictionPolicy::Gdsf),
 			_ => None,
 		}
 	}
+
+	/// Returns LRU-K with the given K, a K of 0 is taken as 1, which is plain LRU.
+	pub fn lru_k(k: usize) -> Self {
+		EvictionPolicy::LruK { k: k.max(1) }
+	}
+
+	/// Compares two eviction candidates that may come from different buckets,
+	/// with the time-dependent priorities taken at `now`.
+	///
+	/// Returns `Ordering::Less` if `a` should be evicted before `b`.
+	/// Sorting needs the same `now` for every comparison, otherwise the order is not total.
+	pub(crate) fn compare_victims<K, V>(&self, a: &Item<K, V>, b: &Item<K, V>, now: Instant) -> Ordering
+	where
+		K: Hash + Eq + Clone,
+		V: Eq + Clone,
+	{
+		match self {
+			// a.accesses / a.age < b.accesses / b.age, the ages are cross-multiplied so a zero age is fine
+			EvictionPolicy::Hyperbolic => {
+				let accesses = |item: &Item<K, V>| item.lfu_counter as u128 + 1;
+				let age = |item: &Item<K, V>| now.saturating_duration_since(item.inserted_at).as_nanos();
+				(accesses(a) * age(b))
+					.cmp(&(accesses(b) * age(a)))
+					.then(a.timestamp.cmp(&b.timestamp))
+			}
+			EvictionPolicy::ClassicLRU | EvictionPolicy::TimestampLRU | EvictionPolicy::Fifo => a.timestamp.cmp(&b.timestamp),
+			EvictionPolicy::Lifo => b.timestamp.cmp(&a.timestamp),
+			EvictionPolicy::Lfu | EvictionPolicy::LfuAging => {
+				a.lfu_counter.cmp(&b.lfu_counter).then(a.timestamp.cmp(&b.timestamp))
+			}
+			// Items without K accesses have an infinite backward K-distance, the subsidiary policy is LRU
+			EvictionPolicy::LruK { k } => a
+				.get_kth_access(*k)
+				.cmp(&b.get_kth_access(*k))
+				.then(a.timestamp.cmp(&b.timestamp)),
+			EvictionPolicy::Lfuda | EvictionPolicy::Gdsf => {
+				let priority = |item: &Item<K, V>| item.policy_state.get_priority();
+				priority(a).total_cmp(&priority(b)).then(a.timestamp.cmp(&b.timestamp))
+			}
+			// Each bucket proposes the item its hand would stop at, unvisited ones go first
+			EvictionPolicy::Sieve | EvictionPolicy::Clock => a
+				.policy_state
+				.is_visited()
+				.cmp(&b.policy_state.is_visited())
+				.then(a.timestamp.cmp(&b.timestamp)),
+			EvictionPolicy::ClockPro => {
+				let flags = |item: &Item<K, V>| (item.policy_state.is_protected(), item.policy_state.is_visited());
+				flags(a).cmp(&flags(b)).then(a.timestamp.cmp(&b.timestamp))
+			}
+			// Probationary items go first
+			EvictionPolicy::TwoQ | EvictionPolicy::Slru => a
+				.policy_state
+				.is_protected()
+				.cmp(&b.policy_state.is_protected())
+				.then(a.timestamp.cmp(&b.timestamp)),
+			EvictionPolicy::Lirs => a
+				.policy_state
+				.is_lir()
+				.cmp(&b.policy_state.is_lir())
+				.then(a.timestamp.cmp(&b.timestamp)),
+			// Without the adaptive target, T1 is emptied first
+			EvictionPolicy::Arc => (a.lfu_counter > 0)
+				.cmp(&(b.lfu_counter > 0))
+				.then(a.timestamp.cmp(&b.timestamp)),
+		}
+	}
 }
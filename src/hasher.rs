@@ -0,0 +1,291 @@
+//! This module defines the hashers that can be plugged into `Dash` and `AssociativeCache`.
+//!
+//! # Examples
+//!
+//! ```
+//! use dash::dash::{dash_settings::DashSettings, Dash};
+//! use dash::hasher::SeededBuildHasher;
+//!
+//! let mut cache: Dash<i64, i64, _> = Dash::with_hasher(DashSettings::default(), SeededBuildHasher::new(42)).unwrap();
+//! cache.put(1, 2);
+//! ```
+
+use std::{
+	collections::hash_map::DefaultHasher,
+	hash::{BuildHasher, BuildHasherDefault, Hasher},
+};
+
+/// SipHash with fixed keys, the hasher used when none is given.
+pub type DefaultBuildHasher = BuildHasherDefault<DefaultHasher>;
+
+/// Multiplier of FxHash.
+const MULTIPLIER: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// Finalizer of MurmurHash3, every input bit affects every output bit.
+fn mix(mut x: u64) -> u64 {
+	x ^= x >> 33;
+	x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+	x ^= x >> 33;
+	x = x.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+	x ^ (x >> 33)
+}
+
+/// A fast hasher for integer keys.
+///
+/// Words are combined with the FxHash rotate-multiply step and the result is finalized,
+/// so the high bits used by the directory are as well distributed as the low ones.
+#[derive(Debug, Clone, Default)]
+pub struct FastHasher {
+	state: u64,
+}
+
+impl Hasher for FastHasher {
+	fn write(&mut self, bytes: &[u8]) {
+		for chunk in bytes.chunks(8) {
+			let mut word = [0; 8];
+			word[..chunk.len()].copy_from_slice(chunk);
+			self.write_u64(u64::from_le_bytes(word));
+		}
+	}
+
+	fn write_u64(&mut self, i: u64) {
+		self.state = (self.state.rotate_left(5) ^ i).wrapping_mul(MULTIPLIER);
+	}
+
+	fn write_i64(&mut self, i: i64) {
+		self.write_u64(i as u64);
+	}
+
+	fn write_u32(&mut self, i: u32) {
+		self.write_u64(i as u64);
+	}
+
+	fn write_i32(&mut self, i: i32) {
+		self.write_u64(i as u64);
+	}
+
+	fn write_usize(&mut self, i: usize) {
+		self.write_u64(i as u64);
+	}
+
+	fn finish(&self) -> u64 {
+		mix(self.state)
+	}
+}
+
+/// Builds `FastHasher`s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FastBuildHasher;
+
+impl BuildHasher for FastBuildHasher {
+	type Hasher = FastHasher;
+
+	fn build_hasher(&self) -> FastHasher {
+		FastHasher::default()
+	}
+}
+
+/// Builds `FastHasher`s whose initial state depends on a seed,
+/// so randomized runs can be reproduced by reusing the seed.
+#[derive(Debug, Clone, Copy)]
+pub struct SeededBuildHasher {
+	seed: u64,
+}
+
+impl SeededBuildHasher {
+	pub fn new(seed: u64) -> Self {
+		SeededBuildHasher { seed }
+	}
+}
+
+impl BuildHasher for SeededBuildHasher {
+	type Hasher = FastHasher;
+
+	fn build_hasher(&self) -> FastHasher {
+		FastHasher { state: mix(self.seed) }
+	}
+}
+
+/// A hasher that returns the key itself, for debugging.
+///
+/// Integer keys keep their bits, so with `HashLayout` small keys all land in the first segment and bucket.
+#[derive(Debug, Clone, Default)]
+pub struct IdentityHasher {
+	state: u64,
+}
+
+impl Hasher for IdentityHasher {
+	fn write(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.state = (self.state << 8) | byte as u64;
+		}
+	}
+
+	fn write_u64(&mut self, i: u64) {
+		self.state = i;
+	}
+
+	fn write_i64(&mut self, i: i64) {
+		self.write_u64(i as u64);
+	}
+
+	fn write_u32(&mut self, i: u32) {
+		self.write_u64(i as u64);
+	}
+
+	fn write_i32(&mut self, i: i32) {
+		self.write_u64(i as u64);
+	}
+
+	fn write_usize(&mut self, i: usize) {
+		self.write_u64(i as u64);
+	}
+
+	fn finish(&self) -> u64 {
+		self.state
+	}
+}
+
+/// Builds `IdentityHasher`s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityBuildHasher;
+
+impl BuildHasher for IdentityBuildHasher {
+	type Hasher = IdentityHasher;
+
+	fn build_hasher(&self) -> IdentityHasher {
+		IdentityHasher::default()
+	}
+}
+
+/// Selects one of the hashers at runtime, e.g. by id from the JNI init functions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HasherKind {
+	#[default]
+	Default,
+	Fast,
+	Seeded(u64),
+	Identity,
+}
+
+impl HasherKind {
+	pub fn from_usize(index: usize, seed: u64) -> Option<Self> {
+		match index {
+			0 => Some(HasherKind::Default),
+			1 => Some(HasherKind::Fast),
+			2 => Some(HasherKind::Seeded(seed)),
+			3 => Some(HasherKind::Identity),
+			_ => None,
+		}
+	}
+}
+
+/// The hasher built by `HasherKind`.
+#[derive(Debug, Clone)]
+pub enum AnyHasher {
+	Default(DefaultHasher),
+	Fast(FastHasher),
+	Identity(IdentityHasher),
+}
+
+/// Forwards the integer writes to the wrapped hasher, so hashers with their own integer handling
+/// (e.g. `IdentityHasher`) hash the same whether they are wrapped or not.
+macro_rules! forward_writes {
+	($($method:ident($ty:ty)),* $(,)?) => {
+		$(
+			fn $method(&mut self, i: $ty) {
+				match self {
+					AnyHasher::Default(hasher) => hasher.$method(i),
+					AnyHasher::Fast(hasher) => hasher.$method(i),
+					AnyHasher::Identity(hasher) => hasher.$method(i),
+				}
+			}
+		)*
+	};
+}
+
+impl Hasher for AnyHasher {
+	fn write(&mut self, bytes: &[u8]) {
+		match self {
+			AnyHasher::Default(hasher) => hasher.write(bytes),
+			AnyHasher::Fast(hasher) => hasher.write(bytes),
+			AnyHasher::Identity(hasher) => hasher.write(bytes),
+		}
+	}
+
+	forward_writes!(
+		write_u8(u8),
+		write_u16(u16),
+		write_u32(u32),
+		write_u64(u64),
+		write_u128(u128),
+		write_usize(usize),
+		write_i8(i8),
+		write_i16(i16),
+		write_i32(i32),
+		write_i64(i64),
+		write_i128(i128),
+		write_isize(isize),
+	);
+
+	fn finish(&self) -> u64 {
+		match self {
+			AnyHasher::Default(hasher) => hasher.finish(),
+			AnyHasher::Fast(hasher) => hasher.finish(),
+			AnyHasher::Identity(hasher) => hasher.finish(),
+		}
+	}
+}
+
+impl BuildHasher for HasherKind {
+	type Hasher = AnyHasher;
+
+	fn build_hasher(&self) -> AnyHasher {
+		match self {
+			HasherKind::Default => AnyHasher::Default(DefaultHasher::new()),
+			HasherKind::Fast => AnyHasher::Fast(FastBuildHasher.build_hasher()),
+			HasherKind::Seeded(seed) => AnyHasher::Fast(SeededBuildHasher::new(*seed).build_hasher()),
+			HasherKind::Identity => AnyHasher::Identity(IdentityBuildHasher.build_hasher()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_seeded_hasher_is_reproducible() {
+		let key: i64 = 1234;
+		assert_eq!(
+			SeededBuildHasher::new(7).hash_one(key),
+			SeededBuildHasher::new(7).hash_one(key)
+		);
+		assert_ne!(
+			SeededBuildHasher::new(7).hash_one(key),
+			SeededBuildHasher::new(8).hash_one(key)
+		);
+	}
+
+	#[test]
+	fn test_identity_hasher_returns_key() {
+		assert_eq!(IdentityBuildHasher.hash_one(1234_i64), 1234);
+		assert_eq!(HasherKind::Identity.hash_one(1234_i64), 1234);
+		assert_eq!(HasherKind::Identity.hash_one(1234_i32), 1234);
+		assert_eq!(HasherKind::Identity.hash_one(1234_usize), 1234);
+	}
+
+	#[test]
+	fn test_hasher_kind_matches_hashers() {
+		let key: i64 = 1234;
+		assert_eq!(HasherKind::Fast.hash_one(key), FastBuildHasher.hash_one(key));
+		assert_eq!(
+			HasherKind::Seeded(7).hash_one(key),
+			SeededBuildHasher::new(7).hash_one(key)
+		);
+		assert_eq!(
+			HasherKind::Default.hash_one(key),
+			DefaultBuildHasher::default().hash_one(key)
+		);
+	}
+}
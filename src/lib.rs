@@ -1,4 +1,7 @@
 pub mod associative_cache;
+pub mod clock;
+pub mod concurrent_dash;
 pub mod dash;
 pub mod eviction_policy;
+pub mod hasher;
 mod shared;
@@ -1,3 +1,11 @@
+pub mod access_history;
+pub mod admission_window;
+pub mod ghost_history;
+pub mod hash_layout;
+pub mod hit_ratio;
 pub mod item;
+pub mod lirs;
+pub mod policy_state;
+pub mod tiny_lfu;
 pub mod traits;
 pub mod utils;
@@ -0,0 +1,59 @@
+//! Access history of the LRU-K eviction policy.
+//!
+//! Every item remembers the times of its last K accesses. The history of an evicted key is retained
+//! by its bucket for a while, so a key that comes back soon does not start over as if it was never seen.
+
+use std::{collections::VecDeque, time::Instant};
+
+#[derive(Debug, Clone, Default)]
+pub struct AccessHistory {
+	/// Times of the last accesses, most recent first.
+	accesses: VecDeque<Instant>,
+}
+
+impl AccessHistory {
+	pub fn new() -> Self {
+		AccessHistory {
+			accesses: VecDeque::new(),
+		}
+	}
+
+	/// Records an access at `time`, keeping only the last `k` accesses.
+	pub fn record(&mut self, time: Instant, k: usize) {
+		self.accesses.push_front(time);
+		self.accesses.truncate(k.max(1));
+	}
+
+	/// Returns the time of the K-th most recent access, or `None` if there were fewer than `k` accesses.
+	pub fn get_kth_access(&self, k: usize) -> Option<Instant> {
+		self.accesses.get(k.max(1) - 1).copied()
+	}
+
+	pub fn len(&self) -> usize {
+		self.accesses.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.accesses.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn test_keeps_last_k_accesses() {
+		let start = Instant::now();
+		let mut history = AccessHistory::new();
+		for offset in 0..3 {
+			history.record(start + Duration::from_secs(offset), 2);
+		}
+
+		assert_eq!(history.len(), 2);
+		assert_eq!(history.get_kth_access(1), Some(start + Duration::from_secs(2)));
+		assert_eq!(history.get_kth_access(2), Some(start + Duration::from_secs(1)));
+		assert_eq!(history.get_kth_access(3), None);
+	}
+}
@@ -0,0 +1,111 @@
+//! Admission window of W-TinyLFU, as used by Caffeine.
+//!
+//! New keys go into a small LRU window instead of the buckets. Only the item the window pushes out has
+//! to win against the victim of the buckets to be admitted, so a burst of new keys can build up the
+//! frequency it needs while it sits in the window, and the TinyLFU filter only judges items that survived it.
+
+use crate::shared::item::Item;
+use std::{
+	collections::{BTreeMap, HashMap},
+	hash::Hash,
+};
+
+/// Share of the capacity of the cache the window takes, the default of Caffeine.
+pub const WINDOW_FRACTION: f64 = 0.01;
+
+#[derive(Debug)]
+pub struct AdmissionWindow<K, V>
+where
+	K: Hash + Eq + Copy,
+	V: Eq + Copy,
+{
+	/// The items of the window, with the stamp of their last access.
+	items: HashMap<K, (u64, Item<K, V>)>,
+	/// The keys of the window by the stamp of their last access, least recently used first.
+	recency: BTreeMap<u64, K>,
+	/// Maximum number of items, at least 1.
+	capacity: usize,
+	next_stamp: u64,
+}
+
+impl<K, V> AdmissionWindow<K, V>
+where
+	K: Hash + Eq + Copy,
+	V: Eq + Copy,
+{
+	/// Creates the window of a cache holding up to `cache_capacity` items.
+	pub fn new(cache_capacity: usize) -> Self {
+		AdmissionWindow {
+			items: HashMap::new(),
+			recency: BTreeMap::new(),
+			capacity: ((cache_capacity as f64 * WINDOW_FRACTION) as usize).max(1),
+			next_stamp: 0,
+		}
+	}
+
+	/// Returns the item with `key` and makes it the most recently used one.
+	pub fn get(&mut self, key: &K) -> Option<&Item<K, V>> {
+		let stamp = self.next_stamp;
+		let (last_stamp, item) = self.items.get_mut(key)?;
+		self.recency.remove(last_stamp);
+		self.recency.insert(stamp, *key);
+		*last_stamp = stamp;
+		self.next_stamp += 1;
+		Some(item)
+	}
+
+	/// Returns the item with `key` without updating it.
+	pub fn peek(&self, key: &K) -> Option<&Item<K, V>> {
+		self.items.get(key).map(|(_, item)| item)
+	}
+
+	/// Puts the item as the most recently used one, returns the item with the same key it replaced, if any.
+	pub fn put(&mut self, item: Item<K, V>) -> Option<Item<K, V>> {
+		let previous_item = self.remove(&item.key);
+		self.recency.insert(self.next_stamp, item.key);
+		self.items.insert(item.key, (self.next_stamp, item));
+		self.next_stamp += 1;
+		previous_item
+	}
+
+	/// Removes and returns the least recently used item if the window is over capacity.
+	pub fn pop_overflow(&mut self) -> Option<Item<K, V>> {
+		if self.items.len() <= self.capacity {
+			return None;
+		}
+		let (_, key) = self.recency.pop_first()?;
+		self.items.remove(&key).map(|(_, item)| item)
+	}
+
+	pub fn remove(&mut self, key: &K) -> Option<Item<K, V>> {
+		let (stamp, item) = self.items.remove(key)?;
+		self.recency.remove(&stamp);
+		Some(item)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_pushes_out_least_recently_used_item() {
+		// 1% of 200 items
+		let mut window: AdmissionWindow<i64, i64> = AdmissionWindow::new(200);
+
+		window.put(Item::new(1, 1));
+		window.put(Item::new(2, 2));
+		assert!(window.pop_overflow().is_none());
+		assert!(window.get(&1).is_some());
+		window.put(Item::new(3, 3));
+		assert_eq!(window.pop_overflow().map(|item| item.key), Some(2));
+
+		// Replacing a key neither grows the window nor pushes anything out
+		assert_eq!(window.put(Item::new(1, 10)).map(|item| item.value), Some(1));
+		assert!(window.pop_overflow().is_none());
+		window.put(Item::new(4, 4));
+		assert_eq!(window.pop_overflow().map(|item| item.key), Some(3));
+		assert_eq!(window.remove(&1).map(|item| item.value), Some(10));
+		assert!(window.peek(&1).is_none());
+	}
+}
@@ -0,0 +1,91 @@
+//! Ghost entries of the adaptive eviction policies.
+//!
+//! 2Q, CLOCK-Pro, S3-FIFO and ARC remember keys they evicted recently, so a key that comes back soon
+//! is treated as popular. Only a 64-bit hash of every key is kept, never the key or its value,
+//! and the oldest hash is forgotten once the history is full.
+
+use std::{collections::VecDeque, mem::size_of};
+
+#[derive(Debug, Clone, Default)]
+pub struct GhostHistory {
+	/// Hashes of the evicted keys, oldest first.
+	fingerprints: VecDeque<u64>,
+	/// Maximum number of hashes, 0 disables the history.
+	capacity: usize,
+}
+
+impl GhostHistory {
+	pub fn new(capacity: usize) -> Self {
+		GhostHistory {
+			fingerprints: VecDeque::new(),
+			capacity,
+		}
+	}
+
+	/// Remembers the hash of an evicted key, forgetting the oldest hash if the history is full.
+	pub fn remember(&mut self, fingerprint: u64) {
+		if self.capacity == 0 {
+			return;
+		}
+		while self.fingerprints.len() >= self.capacity {
+			self.fingerprints.pop_front();
+		}
+		self.fingerprints.push_back(fingerprint);
+	}
+
+	/// Removes the hash from the history, returns whether it was there.
+	pub fn forget(&mut self, fingerprint: u64) -> bool {
+		match self.fingerprints.iter().position(|&ghost| ghost == fingerprint) {
+			Some(position) => {
+				self.fingerprints.remove(position);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Forgets the oldest hash, if any.
+	pub fn forget_oldest(&mut self) -> Option<u64> {
+		self.fingerprints.pop_front()
+	}
+
+	pub fn contains(&self, fingerprint: u64) -> bool {
+		self.fingerprints.contains(&fingerprint)
+	}
+
+	pub fn len(&self) -> usize {
+		self.fingerprints.len()
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// Returns the number of bytes taken by the remembered hashes.
+	pub fn memory_usage(&self) -> usize {
+		self.fingerprints.len() * size_of::<u64>()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_forgets_oldest_when_full() {
+		let mut ghost = GhostHistory::new(2);
+		for fingerprint in 0..3 {
+			ghost.remember(fingerprint);
+		}
+		assert!(!ghost.contains(0));
+		assert_eq!(ghost.memory_usage(), 2 * size_of::<u64>());
+
+		assert!(ghost.forget(1));
+		assert!(!ghost.forget(1));
+		assert_eq!(ghost.len(), 1);
+
+		let mut disabled = GhostHistory::new(0);
+		disabled.remember(0);
+		assert_eq!(disabled.len(), 0);
+	}
+}
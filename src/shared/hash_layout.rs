@@ -0,0 +1,108 @@
+//! This module splits a single 64-bit key hash into disjoint bit ranges, one for every choice Dash makes.
+//!
+//! Taking `hash % size` of the same hash for the segment, the bucket and the stash bucket correlates them,
+//! e.g. with 2 segments and 28 buckets every key of segment 0 has an even hash, so half of its buckets
+//! never receive keys. The layout, from the most significant bit, is:
+//!
+//! ```text
+//! | directory / segment (24) | bucket (24) | stash bucket (8) | fingerprint (8) |
+//! ```
+//!
+//! The directory takes the most significant bits, as in Dash, so a segment split only looks at one more bit.
+
+const FINGERPRINT_SHIFT: u32 = 0;
+const STASH_SHIFT: u32 = FINGERPRINT_SHIFT + HashLayout::FINGERPRINT_BITS;
+const BUCKET_SHIFT: u32 = STASH_SHIFT + HashLayout::STASH_BITS;
+const DIRECTORY_SHIFT: u32 = BUCKET_SHIFT + HashLayout::BUCKET_BITS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashLayout {
+	num_of_segments: usize,
+	num_of_normal_buckets: usize,
+	num_of_stash_buckets: usize,
+}
+
+impl HashLayout {
+	pub const FINGERPRINT_BITS: u32 = 8;
+	pub const STASH_BITS: u32 = 8;
+	pub const BUCKET_BITS: u32 = 24;
+	pub const DIRECTORY_BITS: u32 = u64::BITS - DIRECTORY_SHIFT;
+
+	pub fn new(num_of_segments: usize, num_of_normal_buckets: usize, num_of_stash_buckets: usize) -> Self {
+		HashLayout {
+			num_of_segments,
+			num_of_normal_buckets,
+			num_of_stash_buckets,
+		}
+	}
+
+	/// Returns the 1-byte fingerprint stored next to an item in its bucket.
+	pub fn fingerprint(hash: u64) -> u8 {
+		Self::bits(hash, FINGERPRINT_SHIFT, Self::FINGERPRINT_BITS) as u8
+	}
+
+	/// Returns the segment of a fixed-size segment array.
+	pub fn segment_index(&self, hash: u64) -> usize {
+		(Self::bits(hash, DIRECTORY_SHIFT, Self::DIRECTORY_BITS) % self.num_of_segments as u64) as usize
+	}
+
+	/// Returns the directory entry of an extendible hashing directory with `global_depth`.
+	pub fn directory_index(hash: u64, global_depth: usize) -> usize {
+		debug_assert!(global_depth <= Self::DIRECTORY_BITS as usize);
+		if global_depth == 0 {
+			return 0;
+		}
+		(hash >> (u64::BITS as usize - global_depth)) as usize
+	}
+
+	/// Returns the bit that decides which half a key goes to when a segment with `local_depth` splits.
+	pub fn directory_bit(hash: u64, local_depth: usize) -> bool {
+		debug_assert!(local_depth < Self::DIRECTORY_BITS as usize);
+		(hash >> (u64::BITS as usize - 1 - local_depth)) & 1 == 1
+	}
+
+	/// Returns the target bucket inside a segment.
+	pub fn bucket_index(&self, hash: u64) -> usize {
+		(Self::bits(hash, BUCKET_SHIFT, Self::BUCKET_BITS) % self.num_of_normal_buckets as u64) as usize
+	}
+
+	/// Returns the stash bucket inside a segment.
+	pub fn stash_bucket_index(&self, hash: u64) -> usize {
+		(Self::bits(hash, STASH_SHIFT, Self::STASH_BITS) % self.num_of_stash_buckets as u64) as usize
+	}
+
+	fn bits(hash: u64, shift: u32, len: u32) -> u64 {
+		(hash >> shift) & ((1 << len) - 1)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_ranges_are_disjoint() {
+		let layout = HashLayout::new(4, 28, 4);
+		// Only the bucket range is set
+		let hash = 27 << BUCKET_SHIFT;
+		assert_eq!(layout.bucket_index(hash), 27);
+		assert_eq!(layout.segment_index(hash), 0);
+		assert_eq!(layout.stash_bucket_index(hash), 0);
+		assert_eq!(HashLayout::fingerprint(hash), 0);
+		assert_eq!(
+			HashLayout::directory_index(hash, HashLayout::DIRECTORY_BITS as usize),
+			0
+		);
+	}
+
+	#[test]
+	fn test_directory_uses_most_significant_bits() {
+		let hash = 0b101 << (u64::BITS - 3);
+		assert_eq!(HashLayout::directory_index(hash, 0), 0);
+		assert_eq!(HashLayout::directory_index(hash, 1), 0b1);
+		assert_eq!(HashLayout::directory_index(hash, 3), 0b101);
+		assert!(HashLayout::directory_bit(hash, 0));
+		assert!(!HashLayout::directory_bit(hash, 1));
+		assert!(HashLayout::directory_bit(hash, 2));
+	}
+}
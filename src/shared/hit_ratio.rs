@@ -0,0 +1,80 @@
+use std::fmt::{Display, Formatter};
+
+/// Counts the hits and misses of a cache, both per object and weighted by the object sizes.
+///
+/// The object hit ratio tells how many requests were served from the cache,
+/// the byte hit ratio how much of the requested data was.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HitRatio {
+	pub hits: usize,
+	pub misses: usize,
+	pub hit_bytes: usize,
+	pub miss_bytes: usize,
+}
+
+impl HitRatio {
+	pub fn record_hit(&mut self, size: usize) {
+		self.hits += 1;
+		self.hit_bytes += size;
+	}
+
+	/// Counts a lookup that did not find the object, its size is only known once it is put.
+	pub fn record_miss(&mut self) {
+		self.misses += 1;
+	}
+
+	/// Counts the bytes of an object put after a miss.
+	pub fn record_miss_bytes(&mut self, size: usize) {
+		self.miss_bytes += size;
+	}
+
+	/// Returns the share of the requests that were hits, 0 if there were no requests.
+	pub fn object_hit_ratio(&self) -> f64 {
+		ratio(self.hits, self.hits + self.misses)
+	}
+
+	/// Returns the share of the requested bytes that were hits, 0 if there were no requests.
+	pub fn byte_hit_ratio(&self) -> f64 {
+		ratio(self.hit_bytes, self.hit_bytes + self.miss_bytes)
+	}
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+	if total == 0 {
+		0.0
+	} else {
+		part as f64 / total as f64
+	}
+}
+
+impl Display for HitRatio {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Hit Ratio {{ hits: {}, misses: {}, object: {:.4}, byte: {:.4} }}",
+			self.hits,
+			self.misses,
+			self.object_hit_ratio(),
+			self.byte_hit_ratio()
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_object_and_byte_hit_ratios_differ() {
+		let mut hit_ratio = HitRatio::default();
+		assert_eq!(hit_ratio.object_hit_ratio(), 0.0);
+
+		hit_ratio.record_hit(1);
+		hit_ratio.record_hit(1);
+		hit_ratio.record_hit(1);
+		hit_ratio.record_miss();
+		hit_ratio.record_miss_bytes(97);
+		assert_eq!(hit_ratio.object_hit_ratio(), 0.75);
+		assert_eq!(hit_ratio.byte_hit_ratio(), 0.03);
+	}
+}
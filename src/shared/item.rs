@@ -11,10 +11,12 @@
 //! assert_eq!(item.lfu_counter, 0);
 //! ```
 
+use crate::clock::{Clock, RealClock};
+use crate::shared::policy_state::ItemPolicyState;
 use std::{
 	fmt::{Display, Formatter},
 	hash::Hash,
-	time::Instant,
+	time::{Duration, Instant},
 };
 
 #[derive(Debug, Clone)]
@@ -27,6 +29,14 @@ where
 	pub value: V,
 	pub lfu_counter: usize,
 	pub timestamp: Instant,
+	/// When the item was put into the cache, unlike `timestamp` it is never refreshed.
+	pub inserted_at: Instant,
+	/// What the eviction policy of the bucket holding the item knows about it.
+	pub policy_state: ItemPolicyState,
+	/// Size of the item in bytes, `None` if only the number of items matters.
+	pub size: Option<usize>,
+	/// When the item expires, `None` if it lives until it is evicted.
+	pub expires_at: Option<Instant>,
 }
 
 impl<K, V> Item<K, V>
@@ -34,15 +44,62 @@ where
 	K: Hash + Eq + Clone,
 	V: Eq + Clone,
 {
+	/// Creates an item put into the cache now, according to the wall clock.
 	pub fn new(key: K, value: V) -> Self {
+		Self::new_at(key, value, RealClock.now())
+	}
+
+	/// Creates an item put into the cache at `now`, as given by the clock of the cache.
+	pub fn new_at(key: K, value: V, now: Instant) -> Self {
 		// TODO: initialize lfu_counter and timestamp only when needed by eviction policies
 		Self {
 			key,
 			value,
 			lfu_counter: 0,
-			timestamp: Instant::now(),
+			timestamp: now,
+			inserted_at: now,
+			policy_state: ItemPolicyState::None,
+			size: None,
+			expires_at: None,
+		}
+	}
+
+	/// Makes the item take `size` bytes of the capacity of its bucket.
+	pub fn with_size(self, size: usize) -> Self {
+		Self {
+			size: Some(size),
+			..self
+		}
+	}
+
+	/// Makes the item expire `ttl` after it was put into the cache, so the time comes from the clock it was created with.
+	pub fn with_ttl(self, ttl: Duration) -> Self {
+		Self {
+			expires_at: Some(self.inserted_at + ttl),
+			..self
 		}
 	}
+
+	/// Records an access at the timestamp of the item in its LRU-K history, keeping the last `k` accesses.
+	pub fn record_access(&mut self, k: usize) {
+		let timestamp = self.timestamp;
+		self.policy_state.get_history_mut().record(timestamp, k);
+	}
+
+	/// Returns the time of the K-th most recent access, or `None` if there were fewer than `k` accesses.
+	pub fn get_kth_access(&self, k: usize) -> Option<Instant> {
+		self.policy_state.get_history()?.get_kth_access(k)
+	}
+
+	/// Returns whether the item expired at `now`, an expired item is never returned by the cache.
+	pub fn is_expired(&self, now: Instant) -> bool {
+		self.expires_at.is_some_and(|expires_at| expires_at <= now)
+	}
+
+	/// Returns the size of the item in bytes, an item without a size counts as one byte.
+	pub fn get_size(&self) -> usize {
+		self.size.unwrap_or(1)
+	}
 }
 
 impl<K, V> PartialEq for Item<K, V>
@@ -0,0 +1,92 @@
+//! Bookkeeping of the LIRS eviction policy that does not fit in `Item`.
+//!
+//! The recency stack S holds the LIR items, the recently accessed HIR items and the keys of recently evicted
+//! HIR items (non-resident HIR entries). The queue Q holds the resident HIR items in the order they are evicted.
+
+use std::collections::VecDeque;
+
+/// Share of the capacity kept for resident HIR items.
+const HIR_FRACTION: f64 = 0.01;
+
+/// Returns how many of `capacity` slots the LIR items may take, at least one slot is left for HIR items.
+pub fn get_max_lir_size(capacity: usize) -> usize {
+	let hir_size = ((capacity as f64 * HIR_FRACTION) as usize).max(1);
+	capacity.saturating_sub(hir_size)
+}
+
+#[derive(Debug, Clone)]
+pub struct LirsState<K> {
+	/// The recency stack S, bottom first.
+	stack: VecDeque<K>,
+	/// The resident HIR queue Q, next victim first.
+	queue: VecDeque<K>,
+}
+
+impl<K> LirsState<K>
+where
+	K: Eq + Copy,
+{
+	pub fn new() -> Self {
+		LirsState {
+			stack: VecDeque::new(),
+			queue: VecDeque::new(),
+		}
+	}
+
+	pub fn get_stack(&self) -> &VecDeque<K> {
+		&self.stack
+	}
+
+	/// Returns the position of the key in S counted from the bottom, or `None` if it is not in S.
+	pub fn stack_position(&self, key: &K) -> Option<usize> {
+		self.stack.iter().position(|stack_key| stack_key == key)
+	}
+
+	/// Moves the key to the top of S, pushing it if it is not in S.
+	pub fn move_to_stack_top(&mut self, key: K) {
+		self.remove_from_stack(&key);
+		self.stack.push_back(key);
+	}
+
+	pub fn insert_in_stack(&mut self, position: usize, key: K) {
+		self.stack.insert(position, key);
+	}
+
+	pub fn remove_from_stack(&mut self, key: &K) -> Option<usize> {
+		let position = self.stack_position(key)?;
+		self.stack.remove(position);
+		Some(position)
+	}
+
+	pub fn pop_stack_bottom(&mut self) -> Option<K> {
+		self.stack.pop_front()
+	}
+
+	pub fn get_queue_front(&self) -> Option<&K> {
+		self.queue.front()
+	}
+
+	/// Moves the key to the end of Q, pushing it if it is not in Q.
+	pub fn move_to_queue_end(&mut self, key: K) {
+		self.remove_from_queue(&key);
+		self.queue.push_back(key);
+	}
+
+	pub fn remove_from_queue(&mut self, key: &K) {
+		self.queue.retain(|queue_key| queue_key != key);
+	}
+
+	pub fn clear(&mut self) {
+		self.stack.clear();
+		self.queue.clear();
+	}
+}
+
+impl<K> Default for LirsState<K>
+where
+	K: Eq + Copy,
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
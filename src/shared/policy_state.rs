@@ -0,0 +1,244 @@
+//! State the eviction policies keep on top of the items, per item and per bucket.
+//!
+//! Each policy only needs a few fields, so an item or a bucket carries the variant of its own policy
+//! and the accessors fall back to a neutral value for the others.
+
+use crate::eviction_policy::EvictionPolicy;
+use crate::shared::access_history::AccessHistory;
+use crate::shared::ghost_history::GhostHistory;
+use crate::shared::lirs::LirsState;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Default)]
+pub enum ItemPolicyState {
+	/** The eviction policy keeps nothing on top of the LFU counter and timestamp */
+	#[default]
+	None,
+	/** Reference bit of SIEVE, CLOCK and CLOCK-Pro, and whether the item is protected in SLRU and 2Q or hot in CLOCK-Pro */
+	Flags { visited: bool, protected: bool },
+	/** Whether the item has the LIR status of LIRS, as opposed to HIR */
+	Lirs { lir: bool },
+	/** Key of LFUDA and GDSF, the item with the lowest priority is evicted first */
+	Priority(f64),
+	/** Times of the last accesses of LRU-K */
+	History(Box<AccessHistory>),
+}
+
+impl ItemPolicyState {
+	pub fn is_visited(&self) -> bool {
+		matches!(self, ItemPolicyState::Flags { visited: true, .. })
+	}
+
+	pub fn set_visited(&mut self, visited: bool) {
+		match self {
+			ItemPolicyState::Flags { visited: flag, .. } => *flag = visited,
+			_ => {
+				*self = ItemPolicyState::Flags {
+					visited,
+					protected: false,
+				}
+			}
+		}
+	}
+
+	pub fn is_protected(&self) -> bool {
+		matches!(self, ItemPolicyState::Flags { protected: true, .. })
+	}
+
+	pub fn set_protected(&mut self, protected: bool) {
+		match self {
+			ItemPolicyState::Flags { protected: flag, .. } => *flag = protected,
+			_ => {
+				*self = ItemPolicyState::Flags {
+					visited: false,
+					protected,
+				}
+			}
+		}
+	}
+
+	pub fn is_lir(&self) -> bool {
+		matches!(self, ItemPolicyState::Lirs { lir: true })
+	}
+
+	pub fn set_lir(&mut self, lir: bool) {
+		*self = ItemPolicyState::Lirs { lir };
+	}
+
+	/// Returns the LFUDA or GDSF priority, 0 if it was never set.
+	pub fn get_priority(&self) -> f64 {
+		match self {
+			ItemPolicyState::Priority(priority) => *priority,
+			_ => 0.0,
+		}
+	}
+
+	pub fn set_priority(&mut self, priority: f64) {
+		*self = ItemPolicyState::Priority(priority);
+	}
+
+	/// Returns the LRU-K access history, `None` if no access was recorded.
+	pub fn get_history(&self) -> Option<&AccessHistory> {
+		match self {
+			ItemPolicyState::History(history) => Some(history),
+			_ => None,
+		}
+	}
+
+	/// Returns the LRU-K access history, allocating an empty one if no access was recorded.
+	pub fn get_history_mut(&mut self) -> &mut AccessHistory {
+		if !matches!(self, ItemPolicyState::History(_)) {
+			*self = ItemPolicyState::History(Box::default());
+		}
+		match self {
+			ItemPolicyState::History(history) => history,
+			_ => unreachable!(),
+		}
+	}
+
+	pub fn set_history(&mut self, history: AccessHistory) {
+		*self = ItemPolicyState::History(Box::new(history));
+	}
+}
+
+#[derive(Debug, Clone)]
+pub enum BucketPolicyState<K> {
+	/** The eviction policy keeps nothing in the bucket */
+	None,
+	/** Position of the SIEVE or CLOCK hand, the next item it checks for eviction */
+	Hand(usize),
+	/** Cold hand of CLOCK-Pro and the hashes of the evicted cold keys in their test period */
+	ClockPro { hand: usize, ghost: GhostHistory },
+	/** Hashes of the keys evicted from A1in that 2Q remembers (A1out) */
+	TwoQ { ghost: GhostHistory },
+	/** Recency stack and resident HIR queue of LIRS */
+	Lirs(LirsState<K>),
+	/** Access histories of the keys recently evicted by LRU-K, oldest first */
+	LruK {
+		retained_history: VecDeque<(K, AccessHistory)>,
+	},
+	/** Accesses since the LFU counters were last halved */
+	LfuAging { accesses: usize },
+	/** Cache age of LFUDA and GDSF, the priority of the last evicted item */
+	Age(f64),
+}
+
+impl<K> BucketPolicyState<K>
+where
+	K: Eq + Copy,
+{
+	/// Creates the state of `eviction_policy`, whose ghost history, if any, remembers `ghost_capacity` keys.
+	pub fn new(eviction_policy: &EvictionPolicy, ghost_capacity: usize) -> Self {
+		match eviction_policy {
+			EvictionPolicy::Sieve | EvictionPolicy::Clock => BucketPolicyState::Hand(0),
+			EvictionPolicy::ClockPro => BucketPolicyState::ClockPro {
+				hand: 0,
+				ghost: GhostHistory::new(ghost_capacity),
+			},
+			EvictionPolicy::TwoQ => BucketPolicyState::TwoQ {
+				ghost: GhostHistory::new(ghost_capacity),
+			},
+			EvictionPolicy::Lirs => BucketPolicyState::Lirs(LirsState::new()),
+			EvictionPolicy::LruK { .. } => BucketPolicyState::LruK {
+				retained_history: VecDeque::new(),
+			},
+			EvictionPolicy::LfuAging => BucketPolicyState::LfuAging { accesses: 0 },
+			EvictionPolicy::Lfuda | EvictionPolicy::Gdsf => BucketPolicyState::Age(0.0),
+			_ => BucketPolicyState::None,
+		}
+	}
+
+	/// Returns the position of the SIEVE, CLOCK or CLOCK-Pro hand, 0 for the other policies.
+	pub fn get_hand(&self) -> usize {
+		match self {
+			BucketPolicyState::Hand(hand) | BucketPolicyState::ClockPro { hand, .. } => *hand,
+			_ => 0,
+		}
+	}
+
+	pub fn get_hand_mut(&mut self) -> Option<&mut usize> {
+		match self {
+			BucketPolicyState::Hand(hand) | BucketPolicyState::ClockPro { hand, .. } => Some(hand),
+			_ => None,
+		}
+	}
+
+	pub fn set_hand(&mut self, position: usize) {
+		if let Some(hand) = self.get_hand_mut() {
+			*hand = position;
+		}
+	}
+
+	/// Returns the keys remembered by 2Q or CLOCK-Pro, `None` for the other policies.
+	pub fn get_ghost(&self) -> Option<&GhostHistory> {
+		match self {
+			BucketPolicyState::ClockPro { ghost, .. } | BucketPolicyState::TwoQ { ghost } => Some(ghost),
+			_ => None,
+		}
+	}
+
+	pub fn get_ghost_mut(&mut self) -> Option<&mut GhostHistory> {
+		match self {
+			BucketPolicyState::ClockPro { ghost, .. } | BucketPolicyState::TwoQ { ghost } => Some(ghost),
+			_ => None,
+		}
+	}
+
+	pub fn get_lirs(&self) -> Option<&LirsState<K>> {
+		match self {
+			BucketPolicyState::Lirs(lirs) => Some(lirs),
+			_ => None,
+		}
+	}
+
+	pub fn get_lirs_mut(&mut self) -> Option<&mut LirsState<K>> {
+		match self {
+			BucketPolicyState::Lirs(lirs) => Some(lirs),
+			_ => None,
+		}
+	}
+
+	pub fn get_retained_history(&self) -> Option<&VecDeque<(K, AccessHistory)>> {
+		match self {
+			BucketPolicyState::LruK { retained_history } => Some(retained_history),
+			_ => None,
+		}
+	}
+
+	pub fn get_retained_history_mut(&mut self) -> Option<&mut VecDeque<(K, AccessHistory)>> {
+		match self {
+			BucketPolicyState::LruK { retained_history } => Some(retained_history),
+			_ => None,
+		}
+	}
+
+	/// Returns the cache age of LFUDA and GDSF, 0 for the other policies.
+	pub fn get_age(&self) -> f64 {
+		match self {
+			BucketPolicyState::Age(age) => *age,
+			_ => 0.0,
+		}
+	}
+
+	pub fn set_age(&mut self, new_age: f64) {
+		if let BucketPolicyState::Age(age) = self {
+			*age = new_age;
+		}
+	}
+
+	pub fn get_aging_accesses_mut(&mut self) -> Option<&mut usize> {
+		match self {
+			BucketPolicyState::LfuAging { accesses } => Some(accesses),
+			_ => None,
+		}
+	}
+
+	/// Forgets the state tied to the positions of the items, once they are all taken out of the bucket.
+	/// The remembered keys, histories and age are kept.
+	pub fn clear_positions(&mut self) {
+		self.set_hand(0);
+		if let Some(lirs) = self.get_lirs_mut() {
+			lirs.clear();
+		}
+	}
+}
@@ -0,0 +1,147 @@
+//! TinyLFU admission filter, as used by Caffeine's W-TinyLFU behind the window of `admission_window`.
+//!
+//! The access frequency of every key is estimated by a count-min sketch. A doorkeeper Bloom filter
+//! absorbs the first access of each key so one-hit wonders never reach the sketch. Once `sample_size`
+//! accesses are recorded all the counters are halved and the doorkeeper is cleared, so old popularity fades.
+
+/// Number of rows of the count-min sketch, each one indexed by a different hash of the key.
+const SKETCH_DEPTH: usize = 4;
+/// Counters saturate at this value, like the 4-bit counters of Caffeine.
+const MAX_COUNT: u8 = 15;
+/// The sketch is reset after this many accesses per cached item.
+const SAMPLE_FACTOR: usize = 10;
+/// Number of doorkeeper bits per sketch counter column.
+const DOORKEEPER_BITS_PER_COLUMN: usize = 4;
+/// Odd constants spreading the key hash differently for every row of the sketch.
+const SEEDS: [u64; SKETCH_DEPTH] = [
+	0x9E37_79B9_7F4A_7C15,
+	0xC2B2_AE3D_27D4_EB4F,
+	0x1656_67B1_9E37_79F9,
+	0xFF51_AFD7_ED55_8CCD,
+];
+
+#[derive(Debug)]
+pub struct TinyLfu {
+	/// `SKETCH_DEPTH` rows of `width` counters, stored row after row.
+	counters: Vec<u8>,
+	width: usize,
+	doorkeeper: Vec<u64>,
+	sample_size: usize,
+	additions: usize,
+}
+
+impl TinyLfu {
+	/// Creates a filter sized for a cache holding up to `capacity` items.
+	pub fn new(capacity: usize) -> Self {
+		let width = capacity.max(1).next_power_of_two();
+		let doorkeeper_bits = width * DOORKEEPER_BITS_PER_COLUMN;
+		TinyLfu {
+			counters: vec![0; SKETCH_DEPTH * width],
+			width,
+			doorkeeper: vec![0; doorkeeper_bits.div_ceil(u64::BITS as usize)],
+			sample_size: SAMPLE_FACTOR * capacity.max(1),
+			additions: 0,
+		}
+	}
+
+	/// Records an access to the key with `hash`.
+	pub fn record(&mut self, hash: u64) {
+		if self.doorkeeper_insert(hash) {
+			for row in 0..SKETCH_DEPTH {
+				let index = self.counter_index(hash, row);
+				self.counters[index] = (self.counters[index] + 1).min(MAX_COUNT);
+			}
+		}
+
+		self.additions += 1;
+		if self.additions == self.sample_size {
+			self.reset();
+		}
+	}
+
+	/// Returns the estimated number of recent accesses to the key with `hash`.
+	pub fn frequency(&self, hash: u64) -> usize {
+		let count = (0..SKETCH_DEPTH)
+			.map(|row| self.counters[self.counter_index(hash, row)])
+			.min()
+			.unwrap_or(0);
+		count as usize + self.doorkeeper_contains(hash) as usize
+	}
+
+	/// Returns whether the candidate should replace the victim, i.e. it was accessed more often recently.
+	pub fn admit(&self, candidate_hash: u64, victim_hash: u64) -> bool {
+		self.frequency(candidate_hash) > self.frequency(victim_hash)
+	}
+
+	/// Halves every counter and clears the doorkeeper.
+	fn reset(&mut self) {
+		for counter in self.counters.iter_mut() {
+			*counter >>= 1;
+		}
+		self.doorkeeper.fill(0);
+		self.additions /= 2;
+	}
+
+	fn counter_index(&self, hash: u64, row: usize) -> usize {
+		let spread = (hash ^ SEEDS[row]).wrapping_mul(SEEDS[(row + 1) % SKETCH_DEPTH]);
+		row * self.width + ((spread >> 32) as usize & (self.width - 1))
+	}
+
+	fn doorkeeper_bits(&self, hash: u64) -> [usize; 2] {
+		let num_of_bits = self.doorkeeper.len() * u64::BITS as usize;
+		[
+			hash as usize % num_of_bits,
+			hash.rotate_left(32).wrapping_mul(SEEDS[0]) as usize % num_of_bits,
+		]
+	}
+
+	fn doorkeeper_contains(&self, hash: u64) -> bool {
+		self
+			.doorkeeper_bits(hash)
+			.iter()
+			.all(|&bit| self.doorkeeper[bit / 64] & (1 << (bit % 64)) != 0)
+	}
+
+	/// Adds the key to the doorkeeper, returns whether it was already there.
+	fn doorkeeper_insert(&mut self, hash: u64) -> bool {
+		let contained = self.doorkeeper_contains(hash);
+		for bit in self.doorkeeper_bits(hash) {
+			self.doorkeeper[bit / 64] |= 1 << (bit % 64);
+		}
+		contained
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_frequent_keys_are_admitted() {
+		let mut tiny_lfu = TinyLfu::new(64);
+		for _ in 0..5 {
+			tiny_lfu.record(1);
+		}
+		tiny_lfu.record(2);
+
+		assert!(tiny_lfu.frequency(1) >= 5);
+		assert!(tiny_lfu.admit(1, 2));
+		assert!(!tiny_lfu.admit(2, 1));
+		assert!(!tiny_lfu.admit(3, 2));
+	}
+
+	#[test]
+	fn test_reset_halves_frequencies() {
+		let mut tiny_lfu = TinyLfu::new(4);
+		for _ in 0..9 {
+			tiny_lfu.record(1);
+		}
+		assert_eq!(tiny_lfu.frequency(1), 9);
+
+		// The 40th access triggers the reset
+		for hash in 100..131 {
+			tiny_lfu.record(hash);
+		}
+		assert!(tiny_lfu.frequency(1) <= 4);
+	}
+}
@@ -1,7 +1,14 @@
+use crate::clock::SharedClock;
 use crate::eviction_policy::EvictionPolicy;
+use crate::shared::access_history::AccessHistory;
 use crate::shared::item::Item;
+use crate::shared::lirs::get_max_lir_size;
+use crate::shared::policy_state::{BucketPolicyState, ItemPolicyState};
 use std::{fmt::Debug, hash::Hash, time::Instant};
 
+/// The LFU counters of a bucket using `EvictionPolicy::LfuAging` are halved after this many accesses per slot.
+pub const LFU_AGING_PERIOD: usize = 16;
+
 pub trait Bucket<K, V>
 where
 	K: Hash + Eq + Copy + Debug,
@@ -21,31 +28,177 @@ where
 		usize::MAX
 	}
 
+	/// Returns the maximum total size of the items in the bucket in bytes, `None` if only the number of items is limited.
+	fn get_max_bytes(&self) -> Option<usize> {
+		None
+	}
+
 	/// Returns the eviction policy of the bucket.
 	fn get_eviction_policy(&self) -> &EvictionPolicy;
 
+	/// Returns the clock the timestamps of the items are taken from.
+	fn get_clock(&self) -> &SharedClock;
+
+	/// Returns the current time according to the clock of the bucket.
+	fn now(&self) -> Instant {
+		self.get_clock().now()
+	}
+
+	/// Returns the maximum number of items in the protected region of SLRU and 2Q, or of hot items in CLOCK-Pro.
+	fn get_max_protected_size(&self) -> usize;
+
+	/// Returns what the eviction policy keeps in the bucket on top of the items.
+	fn get_policy_state(&self) -> &BucketPolicyState<K>;
+
+	/// Returns what the eviction policy keeps in the bucket on top of the items.
+	fn get_policy_state_mut(&mut self) -> &mut BucketPolicyState<K>;
+
+	/// Returns the hash the ghost history remembers `key` by.
+	fn get_ghost_fingerprint(&self, key: &K) -> u64;
+
+	// ------------ item storage --------------------------------------------------------
+	// All the changes to the items vector go through these three functions,
+	// so buckets can keep side metadata (e.g. fingerprints) in sync by overriding them.
+
+	/// Appends an item to the end of the items vector.
+	fn push_item(&mut self, item: Item<K, V>) {
+		self.before_push_item(&item);
+		self.get_items_mut().push(item);
+	}
+
+	/// Removes the item located in `position` from the items vector and returns it.
+	fn remove_item(&mut self, position: usize) -> Item<K, V> {
+		self.before_remove_item(position);
+		self.get_items_mut().remove(position)
+	}
+
+	/// Replaces the item located in `position` with `item` without moving any other item and returns it.
+	fn replace_item(&mut self, position: usize, item: Item<K, V>) -> Item<K, V> {
+		std::mem::replace(&mut self.get_items_mut()[position], item)
+	}
+
+	/// Keeps the eviction policy metadata in sync before `item` is appended.
+	/// Overrides of `push_item` must call it.
+	fn before_push_item(&mut self, item: &Item<K, V>) {
+		if let Some(lirs) = self.get_policy_state_mut().get_lirs_mut() {
+			lirs.move_to_stack_top(item.key);
+			if !item.policy_state.is_lir() {
+				lirs.move_to_queue_end(item.key);
+			}
+		}
+	}
+
+	/// Keeps the eviction policy metadata in sync before the item located in `position` is removed,
+	/// e.g. the SIEVE hand stays on the same item.
+	/// Overrides of `remove_item` must call it.
+	fn before_remove_item(&mut self, position: usize) {
+		let key = self.get_items()[position].key;
+		let policy_state = self.get_policy_state_mut();
+		if let Some(hand) = policy_state.get_hand_mut() {
+			if position < *hand {
+				*hand -= 1;
+			}
+		}
+		if let Some(lirs) = policy_state.get_lirs_mut() {
+			lirs.remove_from_stack(&key);
+			lirs.remove_from_queue(&key);
+		}
+	}
+
 	// ----------------------------------------------------------------------------------
 
 	/// Puts an item into the bucket.
 	///
-	/// Returns a tuple containing a reference to the pushed item and an optional evicted item.
-	/// If no item is evicted, the second element of the tuple will be None.
-	/// As a side effect, if the item already exists makes updates according to the eviction policy.
-	fn put(&mut self, item: Item<K, V>) -> (&Item<K, V>, Option<Item<K, V>>) {
+	/// Returns a tuple containing a reference to the pushed item and the evicted items, in eviction order.
+	/// If no item is evicted, the second element of the tuple will be empty.
+	/// If the key already exists its value, size and expiration time are replaced and the item is updated
+	/// according to the eviction policy.
+	///
+	/// With a capacity in bytes a large item may evict several items.
+	/// An item larger than the whole bucket is put anyway, once every other item is evicted.
+	fn put(&mut self, mut item: Item<K, V>) -> (&Item<K, V>, Vec<Item<K, V>>) {
 		// Check if the key already exists in the bucket
-		// TODO: the key should not exist in the bucket. Consider returning an error
 		if let Some(position) = self.get_position(&item.key) {
-			// If the key exists, update item position inside the bucket and return it
-			let pushed_item = self.get_from_position(position);
+			if self.has_room_to_resize(position, &item) {
+				// If the key exists, replace its value, update item position inside the bucket and return it
+				let existing_item = &mut self.get_items_mut()[position];
+				existing_item.value = item.value;
+				existing_item.size = item.size;
+				existing_item.expires_at = item.expires_at;
+				let pushed_item = self.get_from_position(position);
+
+				return (pushed_item, Vec::new());
+			}
+			// The item grew past the room left in the bucket, it is put again like a new item,
+			// keeping what the eviction policy knows about it
+			let previous_item = self.remove_item(position);
+			item = Item {
+				value: item.value,
+				size: item.size,
+				expires_at: item.expires_at,
+				..previous_item
+			};
+		}
+		// Otherwise add the item to the bucket
+		// Checked before evicting, since the eviction may make the ghost forget the key
+		let remembers_evicted_keys = matches!(
+			self.get_eviction_policy(),
+			EvictionPolicy::TwoQ | EvictionPolicy::ClockPro
+		);
+		if remembers_evicted_keys && self.forget_evicted_key(&item.key) {
+			// A key evicted from A1in not long ago goes straight to Am,
+			// a non-resident cold key of CLOCK-Pro accessed during its test period comes back hot
+			item.policy_state.set_protected(true);
+		}
+		if let Some(history) = self.take_retained_history(&item.key) {
+			item.policy_state.set_history(history);
+		}
+		// In place replacement frees a single slot, which may not be enough bytes
+		if self.is_full()
+			&& self.get_max_bytes().is_none()
+			&& matches!(
+				self.get_eviction_policy(),
+				EvictionPolicy::Clock | EvictionPolicy::ClockPro
+			) {
+			// An empty bucket has no victim to replace, the item is pushed as with the other policies
+			if let Some(position) = self.clock_victim_position() {
+				let evicted_item = self.replace_victim(position, item);
+				return (&self.get_items()[position], vec![evicted_item]);
+			}
+		}
+		let mut evicted_items = Vec::new();
+		while !self.has_room_for(&item) && !self.get_items().is_empty() {
+			evicted_items.extend(self.evict_item());
+		}
+		let pushed_item = self.put_according_to_policy(item);
+
+		(pushed_item, evicted_items)
+	}
 
-			(pushed_item, None)
-		} else {
-			// If the key does not exist, add the item to the bucket
-			let evicted_item = if self.is_full() { self.evict_item() } else { None };
-			let pushed_item = self.put_according_to_policy(item);
+	/// Moves the hand of a CLOCK policy to the next victim and returns its position, or `None` if the bucket is empty.
+	fn clock_victim_position(&mut self) -> Option<usize> {
+		match self.get_eviction_policy() {
+			EvictionPolicy::ClockPro => self.sweep_cold_hand(),
+			_ => self.sweep_hand(),
+		}
+	}
 
-			(pushed_item, evicted_item)
+	/// Puts the item in the slot of the victim found by `clock_victim_position`, so no other item moves.
+	///
+	/// Returns the evicted item.
+	fn replace_victim(&mut self, position: usize, item: Item<K, V>) -> Item<K, V> {
+		let is_hot = item.policy_state.is_protected();
+		let evicted_item = self.replace_item(position, item);
+		let size = self.size();
+		self.get_policy_state_mut().set_hand((position + 1) % size);
+		if let EvictionPolicy::ClockPro = self.get_eviction_policy() {
+			// The evicted cold item starts its test period as a non-resident key
+			self.remember_evicted_key(evicted_item.key);
+			if is_hot {
+				self.demote_hot_overflow(position);
+			}
 		}
+		evicted_item
 	}
 
 	fn put_according_to_policy(&mut self, item: Item<K, V>) -> &Item<K, V> {
@@ -54,17 +207,65 @@ where
 			| EvictionPolicy::Lifo
 			| EvictionPolicy::ClassicLRU
 			| EvictionPolicy::TimestampLRU
-			| EvictionPolicy::Lfu => {
-				self.get_items_mut().push(item);
-				&self.get_items().last().unwrap()
+			| EvictionPolicy::Lfu
+			| EvictionPolicy::Sieve
+			| EvictionPolicy::Hyperbolic
+			| EvictionPolicy::Arc
+			| EvictionPolicy::TwoQ
+			| EvictionPolicy::Slru
+			| EvictionPolicy::Clock => {
+				self.push_item(item);
+				self.get_items().last().unwrap()
+			}
+			EvictionPolicy::LruK { k } => {
+				let k = *k;
+				let mut item = item;
+				item.record_access(k);
+				self.push_item(item);
+				self.get_items().last().unwrap()
+			}
+			EvictionPolicy::LfuAging => {
+				self.push_item(item);
+				self.age_lfu_counters();
+				self.get_items().last().unwrap()
+			}
+			EvictionPolicy::Lfuda | EvictionPolicy::Gdsf => {
+				let mut item = item;
+				let priority = self.get_priority(&item);
+				item.policy_state.set_priority(priority);
+				self.push_item(item);
+				self.get_items().last().unwrap()
+			}
+			EvictionPolicy::ClockPro => {
+				let is_hot = item.policy_state.is_protected();
+				self.push_item(item);
+				if is_hot {
+					self.demote_hot_overflow(self.size() - 1);
+				}
+				self.get_items().last().unwrap()
+			}
+			EvictionPolicy::Lirs => {
+				let mut item = item;
+				// Every item is LIR until the LIR set is full, then only the ones with a non-resident entry in S,
+				// which the segment may have found in another bucket
+				let lir_size = self.lir_size();
+				let in_stack = self.is_in_lirs_stack(&item.key) || item.policy_state.is_lir();
+				item
+					.policy_state
+					.set_lir(lir_size < get_max_lir_size(self.get_max_size()) || in_stack);
+				self.push_item(item);
+				self.demote_lir_overflow();
+				self.prune_lirs_stack();
+				self.get_items().last().unwrap()
 			}
 		}
 	}
 
-	/// Removes the key-value pair with the given key from the bucket.
-	/// If the bucket is empty or the key is not found, this function does nothing.
-	fn remove(&mut self, key: &K) {
-		self.get_items_mut().retain(|item| item.key != *key);
+	/// Removes the key-value pair with the given key from the bucket and returns it.
+	/// If the bucket is empty or the key is not found, this function does nothing and returns `None`.
+	fn remove(&mut self, key: &K) -> Option<Item<K, V>> {
+		let position = self.get_position(key)?;
+		Some(self.remove_item(position))
 	}
 
 	fn get(&mut self, key: &K) -> Option<&Item<K, V>> {
@@ -84,11 +285,250 @@ where
 		match self.get_eviction_policy() {
 			EvictionPolicy::Fifo | EvictionPolicy::Lifo => &self.get_items()[position],
 			EvictionPolicy::ClassicLRU | EvictionPolicy::TimestampLRU => self.get_and_update_lru_item(position),
-			EvictionPolicy::Lfu => {
+			EvictionPolicy::Lfu | EvictionPolicy::Hyperbolic => {
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.lfu_counter += 1;
+				item.timestamp = now;
+				&self.get_items()[position]
+			}
+			EvictionPolicy::LfuAging => {
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.lfu_counter += 1;
+				item.timestamp = now;
+				self.age_lfu_counters();
+				&self.get_items()[position]
+			}
+			EvictionPolicy::Lfuda | EvictionPolicy::Gdsf => {
 				self.get_items_mut()[position].lfu_counter += 1;
+				let priority = self.get_priority(&self.get_items()[position]);
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.policy_state.set_priority(priority);
+				item.timestamp = now;
+				&self.get_items()[position]
+			}
+			EvictionPolicy::Sieve | EvictionPolicy::Clock | EvictionPolicy::ClockPro => {
+				self.get_items_mut()[position].policy_state.set_visited(true);
+				&self.get_items()[position]
+			}
+			EvictionPolicy::Arc => {
+				// A hit moves the item from T1 to T2, or to the most recently used end of T2
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.lfu_counter += 1;
+				item.timestamp = now;
+				&self.get_items()[position]
+			}
+			EvictionPolicy::TwoQ => {
+				// A1in is a FIFO, only hits in Am refresh the item
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				if item.policy_state.is_protected() {
+					item.timestamp = now;
+				}
+				&self.get_items()[position]
+			}
+			EvictionPolicy::Lirs => {
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.timestamp = now;
+				let key = item.key;
+				let is_lir = item.policy_state.is_lir();
+				let in_stack = self.is_in_lirs_stack(&key);
+				let lirs = self.get_policy_state_mut().get_lirs_mut().unwrap();
+				lirs.move_to_stack_top(key);
+				if is_lir {
+					self.prune_lirs_stack();
+				} else if in_stack {
+					// A HIR item with a recency lower than the bottom LIR item becomes LIR
+					lirs.remove_from_queue(&key);
+					self.get_items_mut()[position].policy_state.set_lir(true);
+					self.demote_lir_overflow();
+					self.prune_lirs_stack();
+				} else {
+					lirs.move_to_queue_end(key);
+				}
+				&self.get_items()[position]
+			}
+			EvictionPolicy::LruK { k } => {
+				let k = *k;
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.timestamp = now;
+				item.record_access(k);
 				&self.get_items()[position]
 			}
+			EvictionPolicy::Slru => {
+				let now = self.now();
+				let item = &mut self.get_items_mut()[position];
+				item.policy_state.set_protected(true);
+				item.timestamp = now;
+				self.demote_protected_overflow();
+				&self.get_items()[position]
+			}
+		}
+	}
+
+	/// Returns the priority of the item according to LFUDA or GDSF, given its current LFU counter.
+	fn get_priority(&self, item: &Item<K, V>) -> f64 {
+		let frequency = (item.lfu_counter + 1) as f64;
+		match self.get_eviction_policy() {
+			// Smaller items are worth keeping, many of them fit in the room of a large one
+			EvictionPolicy::Gdsf => self.get_policy_state().get_age() + frequency / item.get_size().max(1) as f64,
+			_ => self.get_policy_state().get_age() + frequency,
+		}
+	}
+
+	/// Counts an access and halves the LFU counters of all the items every `LFU_AGING_PERIOD` accesses per slot.
+	fn age_lfu_counters(&mut self) {
+		let period = self.get_max_size().saturating_mul(LFU_AGING_PERIOD);
+		let Some(accesses) = self.get_policy_state_mut().get_aging_accesses_mut() else {
+			return;
+		};
+		*accesses += 1;
+		if *accesses >= period {
+			*accesses = 0;
+			for item in self.get_items_mut().iter_mut() {
+				item.lfu_counter /= 2;
+			}
+		}
+	}
+
+	/// Returns the number of items in the protected region.
+	fn protected_size(&self) -> usize {
+		self
+			.get_items()
+			.iter()
+			.filter(|item| item.policy_state.is_protected())
+			.count()
+	}
+
+	/// Returns the number of LIR items.
+	fn lir_size(&self) -> usize {
+		self
+			.get_items()
+			.iter()
+			.filter(|item| item.policy_state.is_lir())
+			.count()
+	}
+
+	/// Returns whether the key has an entry in the LIRS stack S, resident or not.
+	fn is_in_lirs_stack(&self, key: &K) -> bool {
+		self
+			.get_policy_state()
+			.get_lirs()
+			.is_some_and(|lirs| lirs.stack_position(key).is_some())
+	}
+
+	/// Moves the least recently used items of the protected region to the probationary region
+	/// until the protected region fits in its maximum size.
+	fn demote_protected_overflow(&mut self) {
+		while self.protected_size() > self.get_max_protected_size() {
+			let now = self.now();
+			let demoted_item = self
+				.get_items_mut()
+				.iter_mut()
+				.filter(|item| item.policy_state.is_protected())
+				.min_by_key(|item| item.timestamp)
+				.unwrap();
+			// The demoted item becomes the most recently used probationary item
+			demoted_item.policy_state.set_protected(false);
+			demoted_item.timestamp = now;
+		}
+	}
+
+	/// Turns the LIR items at the bottom of S into resident HIR items until the LIR set fits in its maximum size.
+	fn demote_lir_overflow(&mut self) {
+		let max_lir_size = get_max_lir_size(self.get_max_size());
+		while self.lir_size() > max_lir_size {
+			self.prune_lirs_stack();
+			let Some(lirs) = self.get_policy_state_mut().get_lirs_mut() else {
+				return;
+			};
+			let Some(key) = lirs.pop_stack_bottom() else {
+				return;
+			};
+			lirs.move_to_queue_end(key);
+			let position = self.get_position(&key).unwrap();
+			self.get_items_mut()[position].policy_state.set_lir(false);
+		}
+	}
+
+	/// Pops the entries at the bottom of S until a resident LIR item is at the bottom.
+	fn prune_lirs_stack(&mut self) {
+		while let Some(&key) = self
+			.get_policy_state()
+			.get_lirs()
+			.and_then(|lirs| lirs.get_stack().front())
+		{
+			match self.get_position(&key) {
+				Some(position) if self.get_items()[position].policy_state.is_lir() => return,
+				_ => {
+					self.get_policy_state_mut().get_lirs_mut().unwrap().pop_stack_bottom();
+				}
+			}
+		}
+	}
+
+	/// Removes the items that expired at `now` and returns how many there were.
+	///
+	/// Expired items are removed rather than evicted, so the eviction policy does not remember them.
+	fn remove_expired_items(&mut self, now: Instant) -> usize {
+		let mut removed = 0;
+		for position in (0..self.get_items().len()).rev() {
+			if self.get_items()[position].is_expired(now) {
+				self.remove_item(position);
+				removed += 1;
+			}
 		}
+		removed
+	}
+
+	/// Removes the key from the keys remembered by the eviction policy, returns whether it was there.
+	fn forget_evicted_key(&mut self, key: &K) -> bool {
+		let fingerprint = self.get_ghost_fingerprint(key);
+		self
+			.get_policy_state_mut()
+			.get_ghost_mut()
+			.is_some_and(|ghost| ghost.forget(fingerprint))
+	}
+
+	/// Remembers the key of an item evicted by the eviction policy, forgetting the oldest key if needed.
+	fn remember_evicted_key(&mut self, key: K) {
+		let fingerprint = self.get_ghost_fingerprint(&key);
+		if let Some(ghost) = self.get_policy_state_mut().get_ghost_mut() {
+			ghost.remember(fingerprint);
+		}
+	}
+
+	/// Retains the access history of a key evicted by LRU-K, forgetting the oldest history if needed.
+	///
+	/// The retained-information period spans as many evicted keys as the bucket holds items.
+	fn retain_history(&mut self, key: K, history: AccessHistory) {
+		let max_retained_size = self.get_max_size().max(1);
+		let Some(retained_history) = self.get_policy_state_mut().get_retained_history_mut() else {
+			return;
+		};
+		if retained_history.len() == max_retained_size {
+			retained_history.pop_front();
+		}
+		retained_history.push_back((key, history));
+	}
+
+	/// Removes the retained access history of the key and returns it, or `None` if it was not retained.
+	fn take_retained_history(&mut self, key: &K) -> Option<AccessHistory> {
+		let position = self
+			.get_policy_state()
+			.get_retained_history()?
+			.iter()
+			.position(|(retained_key, _)| retained_key == key)?;
+		self
+			.get_policy_state_mut()
+			.get_retained_history_mut()?
+			.remove(position)
+			.map(|(_, history)| history)
 	}
 
 	/// Returns a reference to the item in position `position`, or `None` if the item is not found.
@@ -96,12 +536,15 @@ where
 	fn get_and_update_lru_item(&mut self, position: usize) -> &Item<K, V> {
 		match self.get_eviction_policy() {
 			EvictionPolicy::ClassicLRU => {
-				let item = self.get_items_mut().remove(position);
-				self.get_items_mut().push(item);
-				&self.get_items().last().unwrap()
+				let mut item = self.remove_item(position);
+				// The timestamp lets victims be compared across buckets
+				item.timestamp = self.now();
+				self.push_item(item);
+				self.get_items().last().unwrap()
 			}
 			EvictionPolicy::TimestampLRU => {
-				self.get_items_mut()[position].timestamp = Instant::now();
+				let now = self.now();
+				self.get_items_mut()[position].timestamp = now;
 				&self.get_items()[position]
 			}
 			_ => panic!("This function should only be called with LRU eviction policies"),
@@ -110,6 +553,178 @@ where
 
 	/// Evicts an item from the bucket according to the eviction policy and return it.
 	fn evict_item(&mut self) -> Option<Item<K, V>> {
+		let position = match self.get_eviction_policy() {
+			EvictionPolicy::Sieve | EvictionPolicy::Clock => self.sweep_hand()?,
+			EvictionPolicy::ClockPro => self.sweep_cold_hand()?,
+			_ => self.get_victim_position()?,
+		};
+		Some(self.evict_from_position(position))
+	}
+
+	/// Evicts the item located in `position` and returns it, whichever part of the cache picked it as the victim.
+	///
+	/// Unlike `remove_item`, the eviction policy learns about the eviction, e.g. LFUDA ages the bucket.
+	/// Every eviction must go through this function, a removal that is not an eviction must not.
+	fn evict_from_position(&mut self, position: usize) -> Item<K, V> {
+		// A victim picked outside the bucket may lie past the hand
+		match self.get_eviction_policy() {
+			EvictionPolicy::Sieve | EvictionPolicy::Clock => self.sweep_hand_to(position),
+			EvictionPolicy::ClockPro => self.sweep_cold_hand_to(position),
+			_ => {}
+		}
+		let key = self.get_items()[position].key;
+		let stack_position = self
+			.get_policy_state()
+			.get_lirs()
+			.and_then(|lirs| lirs.stack_position(&key));
+		let evicted_item = self.remove_item(position);
+		match self.get_eviction_policy() {
+			EvictionPolicy::TwoQ if !evicted_item.policy_state.is_protected() => self.remember_evicted_key(evicted_item.key),
+			// The evicted item starts its test period as a non-resident key, as in `replace_victim`
+			EvictionPolicy::ClockPro => self.remember_evicted_key(evicted_item.key),
+			EvictionPolicy::Lfuda | EvictionPolicy::Gdsf => {
+				let priority = evicted_item.policy_state.get_priority();
+				self.get_policy_state_mut().set_age(priority)
+			}
+			EvictionPolicy::LruK { .. } => {
+				let history = evicted_item.policy_state.get_history().cloned().unwrap_or_default();
+				self.retain_history(evicted_item.key, history)
+			}
+			EvictionPolicy::Lirs => {
+				// The evicted item keeps its place in S as a non-resident HIR entry
+				if let (Some(stack_position), Some(lirs)) = (stack_position, self.get_policy_state_mut().get_lirs_mut()) {
+					lirs.insert_in_stack(stack_position, key);
+					self.forget_non_resident_overflow();
+				}
+			}
+			_ => {}
+		}
+		evicted_item
+	}
+
+	/// Drops the oldest non-resident HIR entries of S once there are more of them than the bucket holds items.
+	fn forget_non_resident_overflow(&mut self) {
+		let Some(lirs) = self.get_policy_state().get_lirs() else {
+			return;
+		};
+		let non_resident_keys: Vec<K> = lirs
+			.get_stack()
+			.iter()
+			.filter(|key| self.get_position(key).is_none())
+			.copied()
+			.collect();
+		let overflow = non_resident_keys.len().saturating_sub(self.get_max_size());
+		let lirs = self.get_policy_state_mut().get_lirs_mut().unwrap();
+		for key in &non_resident_keys[..overflow] {
+			lirs.remove_from_stack(key);
+		}
+	}
+
+	/// Moves the SIEVE hand to the first unvisited item, clearing the visited bit of every item it passes over.
+	///
+	/// Returns the new position of the hand, or `None` if the bucket is empty.
+	fn sweep_hand(&mut self) -> Option<usize> {
+		let size = self.size();
+		if size == 0 {
+			return None;
+		}
+		let mut hand = self.get_policy_state().get_hand() % size;
+		while self.get_items()[hand].policy_state.is_visited() {
+			self.get_items_mut()[hand].policy_state.set_visited(false);
+			hand = (hand + 1) % size;
+		}
+		self.get_policy_state_mut().set_hand(hand);
+		Some(hand)
+	}
+
+	/// Moves the SIEVE hand to `position`, clearing the visited bit of every item it passes over on the way.
+	fn sweep_hand_to(&mut self, position: usize) {
+		let size = self.size();
+		let mut hand = self.get_policy_state().get_hand() % size;
+		while hand != position {
+			self.get_items_mut()[hand].policy_state.set_visited(false);
+			hand = (hand + 1) % size;
+		}
+		self.get_policy_state_mut().set_hand(position);
+	}
+
+	/// Moves the cold hand of CLOCK-Pro to the first cold item that was not referenced.
+	///
+	/// A referenced cold item passed over is still in its test period, so it becomes hot.
+	/// Returns the new position of the hand, the item it points to is evicted even if it is hot,
+	/// or `None` if the bucket is empty.
+	fn sweep_cold_hand(&mut self) -> Option<usize> {
+		let size = self.size();
+		if size == 0 {
+			return None;
+		}
+		let mut hand = self.get_policy_state().get_hand() % size;
+		// The hot items left cold items behind within two rounds, unless every item is hot
+		for _ in 0..2 * size {
+			let item = &mut self.get_items_mut()[hand];
+			if !item.policy_state.is_protected() {
+				if !item.policy_state.is_visited() {
+					break;
+				}
+				item.policy_state = ItemPolicyState::Flags {
+					visited: false,
+					protected: true,
+				};
+				self.demote_hot_overflow(hand);
+			}
+			hand = (hand + 1) % size;
+		}
+		self.get_policy_state_mut().set_hand(hand);
+		Some(hand)
+	}
+
+	/// Moves the cold hand of CLOCK-Pro to `position`, the referenced cold items it passes over become hot
+	/// as in `sweep_cold_hand`.
+	fn sweep_cold_hand_to(&mut self, position: usize) {
+		let size = self.size();
+		let mut hand = self.get_policy_state().get_hand() % size;
+		while hand != position {
+			let item = &mut self.get_items_mut()[hand];
+			if !item.policy_state.is_protected() && item.policy_state.is_visited() {
+				item.policy_state = ItemPolicyState::Flags {
+					visited: false,
+					protected: true,
+				};
+				self.demote_hot_overflow(hand);
+			}
+			hand = (hand + 1) % size;
+		}
+		self.get_policy_state_mut().set_hand(position);
+	}
+
+	/// Runs the hot hand of CLOCK-Pro from the cold hand until there are no more hot items than allowed.
+	///
+	/// A referenced hot item passed over gets its reference bit cleared, the first one that was not referenced becomes cold.
+	/// The item located in `new_hot_position` just became hot and is skipped. At least one item is always kept cold.
+	fn demote_hot_overflow(&mut self, new_hot_position: usize) {
+		let size = self.size();
+		let max_hot_size = self.get_max_protected_size().min(size.saturating_sub(1));
+		let mut hot_hand = self.get_policy_state().get_hand() % size.max(1);
+		// Two rounds are enough to clear the reference bits and then demote, unless only the new hot item is left
+		for _ in 0..2 * size {
+			if self.protected_size() <= max_hot_size {
+				return;
+			}
+			let item = &mut self.get_items_mut()[hot_hand];
+			if item.policy_state.is_protected() && hot_hand != new_hot_position {
+				if item.policy_state.is_visited() {
+					item.policy_state.set_visited(false);
+				} else {
+					item.policy_state.set_protected(false);
+				}
+			}
+			hot_hand = (hot_hand + 1) % size;
+		}
+	}
+
+	/// Returns the position of the item the eviction policy would evict next,
+	/// or `None` if the bucket is empty.
+	fn get_victim_position(&self) -> Option<usize> {
 		if self.get_items().is_empty() {
 			return None;
 		}
@@ -117,30 +732,92 @@ where
 		match self.get_eviction_policy() {
 			EvictionPolicy::ClassicLRU => {
 				// TODO: this is in O(n). there could be a more performant way to do that
-				Some(self.get_items_mut().remove(0))
+				Some(0)
 			}
 			EvictionPolicy::TimestampLRU => {
 				let (min_timestamp_index, _) = self
 					.get_items()
 					.iter()
 					.enumerate()
-					.min_by_key(|(_, item)| item.timestamp.elapsed())
+					.min_by_key(|(_, item)| item.timestamp)
 					.unwrap();
-				Some(self.get_items_mut().remove(min_timestamp_index))
+				Some(min_timestamp_index)
 			}
 			EvictionPolicy::Fifo => {
 				// TODO: this is in O(n). there could be a more performant way to do that
-				Some(self.get_items_mut().remove(0))
+				Some(0)
+			}
+			EvictionPolicy::Lifo => Some(self.size() - 1),
+			EvictionPolicy::Lfu
+			| EvictionPolicy::LfuAging
+			| EvictionPolicy::Lfuda
+			| EvictionPolicy::Gdsf
+			| EvictionPolicy::Hyperbolic => {
+				let eviction_policy = self.get_eviction_policy();
+				let now = self.now();
+				let (min_index, _) = self
+					.get_items()
+					.iter()
+					.enumerate()
+					.min_by(|(_, a), (_, b)| eviction_policy.compare_victims(a, b, now))
+					.unwrap();
+				Some(min_index)
+			}
+			EvictionPolicy::ClockPro => {
+				// Referenced cold items become hot as the hand passes over them, unless every item is hot
+				let size = self.size();
+				let hand = self.get_policy_state().get_hand() % size;
+				let position = (0..size)
+					.map(|offset| (hand + offset) % size)
+					.find(|&position| {
+						let policy_state = &self.get_items()[position].policy_state;
+						!policy_state.is_protected() && !policy_state.is_visited()
+					})
+					.unwrap_or(hand);
+				Some(position)
+			}
+			EvictionPolicy::Sieve | EvictionPolicy::Clock => {
+				// The hand walks from the oldest item to the newest and wraps around.
+				// If every item was visited the hand clears them all and stops where it started.
+				let size = self.size();
+				let hand = self.get_policy_state().get_hand() % size;
+				let position = (0..size)
+					.map(|offset| (hand + offset) % size)
+					.find(|&position| !self.get_items()[position].policy_state.is_visited())
+					.unwrap_or(hand);
+				Some(position)
+			}
+			EvictionPolicy::TwoQ => {
+				// A1in is emptied first once it grows over its share of the bucket, or if Am is empty
+				let max_probationary_size = self.get_max_size().saturating_sub(self.get_max_protected_size());
+				let evict_probationary = self.size() - self.protected_size() > max_probationary_size;
+				let (victim_index, _) = self
+					.get_items()
+					.iter()
+					.enumerate()
+					.min_by_key(|(_, item)| (item.policy_state.is_protected() == evict_probationary, item.timestamp))
+					.unwrap();
+				Some(victim_index)
 			}
-			EvictionPolicy::Lifo => self.get_items_mut().pop(),
-			EvictionPolicy::Lfu => {
-				let (min_lfu_counter_index, _) = self
+			EvictionPolicy::Lirs => {
+				// The front of Q, or the oldest item if every item is LIR
+				let queue_front = self
+					.get_policy_state()
+					.get_lirs()
+					.and_then(|lirs| lirs.get_queue_front());
+				let position = queue_front.and_then(|key| self.get_position(key));
+				Some(position.unwrap_or(0))
+			}
+			EvictionPolicy::Arc | EvictionPolicy::Slru | EvictionPolicy::LruK { .. } => {
+				let eviction_policy = self.get_eviction_policy();
+				let now = self.now();
+				let (victim_index, _) = self
 					.get_items()
 					.iter()
 					.enumerate()
-					.min_by_key(|(_, item)| item.lfu_counter)
+					.min_by(|(_, a), (_, b)| eviction_policy.compare_victims(a, b, now))
 					.unwrap();
-				Some(self.get_items_mut().remove(min_lfu_counter_index))
+				Some(victim_index)
 			}
 		}
 	}
@@ -154,6 +831,28 @@ where
 	fn size(&self) -> usize {
 		self.get_items().len()
 	}
+
+	/// Returns the total size of the items in the bucket in bytes.
+	fn used_bytes(&self) -> usize {
+		self.get_items().iter().map(|item| item.get_size()).sum()
+	}
+
+	/// Returns whether the item located in `position` can be replaced by `item`, which may have another size,
+	/// without evicting anything.
+	fn has_room_to_resize(&self, position: usize, item: &Item<K, V>) -> bool {
+		let previous_size = self.get_items()[position].get_size();
+		self
+			.get_max_bytes()
+			.is_none_or(|max_bytes| self.used_bytes() - previous_size + item.get_size() <= max_bytes)
+	}
+
+	/// Returns whether `item` can be put into the bucket without evicting anything.
+	fn has_room_for(&self, item: &Item<K, V>) -> bool {
+		!self.is_full()
+			&& self
+				.get_max_bytes()
+				.is_none_or(|max_bytes| self.used_bytes() + item.get_size() <= max_bytes)
+	}
 }
 
 // TODO: fix this to use blanket implementation
@@ -1,28 +1,64 @@
-use std::collections::hash_map::DefaultHasher;
 use std::fmt::Debug;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash};
 
+use super::hash_layout::HashLayout;
+use super::item::Item;
 use log::debug;
 
-pub fn get_index<K>(key: &K, size: usize) -> usize
+pub fn get_index<K, S>(key: &K, size: usize, hash_builder: &S) -> usize
 where
 	K: Hash + Debug,
+	S: BuildHasher,
 {
-	let hash = hash(key);
-	hash % size
+	get_index_from_hash(hash(key, hash_builder), size)
 }
 
-pub fn hash<K>(key: &K) -> usize
+pub fn get_index_from_hash(hash: u64, size: usize) -> usize {
+	(hash % size as u64) as usize
+}
+
+pub fn hash<K, S>(key: &K, hash_builder: &S) -> u64
 where
 	K: Hash + Debug,
+	S: BuildHasher,
 {
-	let mut hasher = DefaultHasher::new();
-	key.hash(&mut hasher);
-	let hash = hasher.finish() as usize;
+	let hash = hash_builder.hash_one(key);
 	debug!("Hashed key {:?} to {}", key, hash);
 	hash
 }
 
+/// Returns a 1-byte fingerprint of the key, used to skip full key comparisons inside a bucket.
+///
+/// The fingerprint bits are disjoint from the ones used to pick a bucket, see `HashLayout`.
+pub fn get_fingerprint<K, S>(key: &K, hash_builder: &S) -> u8
+where
+	K: Hash + Debug,
+	S: BuildHasher,
+{
+	HashLayout::fingerprint(hash(key, hash_builder))
+}
+
+/// Returns the key-value pairs of the evicted items, in eviction order.
+pub fn into_pairs<K, V>(items: Vec<Item<K, V>>) -> Vec<(K, V)>
+where
+	K: Hash + Eq + Clone,
+	V: Eq + Clone,
+{
+	items.into_iter().map(|item| (item.key, item.value)).collect()
+}
+
+/// Returns how many of `capacity` slots the protected region of SLRU and 2Q may take.
+pub fn get_protected_size(capacity: usize, protected_fraction: f64) -> usize {
+	(capacity as f64 * protected_fraction).round() as usize
+}
+
+/// Returns how many evicted keys a ghost history of a region holding `capacity` items remembers.
+///
+/// Defaults to half the capacity, as suggested for the A1out list of 2Q.
+pub fn get_ghost_capacity(capacity: usize, ghost_capacity: Option<usize>) -> usize {
+	ghost_capacity.unwrap_or((capacity / 2).max(1))
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -31,7 +67,7 @@ mod tests {
 	fn test_get_index() {
 		let hash = 123;
 		let size = 10;
-		let index = get_index(hash, size);
+		let index = get_index_from_hash(hash, size);
 		// 123 % 10 = 3
 		assert_eq!(index, 3);
 	}